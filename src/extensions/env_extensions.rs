@@ -1,98 +1,1058 @@
 #![allow(non_upper_case_globals)]
-use soroban_sdk::storage::{Instance, Persistent};
-use soroban_sdk::{panic_with_error, Address, Env};
+use soroban_sdk::storage::{Instance, Persistent, Temporary};
+use soroban_sdk::{
+    panic_with_error, Address, Bytes, BytesN, Env, IntoVal, Map, String, Symbol, TryFromVal, Val,
+    Vec,
+};
 
 use crate::types;
 
-use types::{error::Error, subscription::Subscription};
+use types::{
+    error::Error, legacy_subscription::LegacySubscription, rounding_mode::RoundingMode,
+    subscription::Subscription, subscription_summary::SubscriptionSummary,
+    threshold_kind::ThresholdKind, ticker_asset::TickerAsset,
+};
 const ADMIN_KEY: &str = "admin";
+const WORKER_KEY: &str = "worker";
 const BASE_FEE: &str = "base_fee";
+const MIN_FEE_FLOOR_KEY: &str = "min_fee_floor";
+const MIN_DEPOSIT_KEY: &str = "min_deposit";
+const RECOVERY_WINDOW_KEY: &str = "recovery_window";
+const ROUNDING_MODE_KEY: &str = "rounding";
+const MIN_FEE_CHANGE_INTERVAL_KEY: &str = "fee_change_interval";
+const LAST_FEE_CHANGE_KEY: &str = "last_fee_change";
+const WEBHOOK_PREFIX_KEY: &str = "webhook_prefix";
 const LAST_SUBSCRIPTION_ID: &str = "last";
+const SUBSCRIPTION_COUNT_KEY: &str = "count";
+const ACTIVE_COUNT_KEY: &str = "active_count";
+const TOTAL_LOCKED_KEY: &str = "total_locked";
+const AGGREGATES_REBUILD_KEY: &str = "rebuild";
+const USER_ID_START_KEY: &str = "user_id_start";
+const BALANCE_HISTORY_LEN_KEY: &str = "history_len";
+const BALANCE_HISTORY_KEY: &str = "history";
+const CONFIG_HASH_KEY: &str = "config_hash";
+const FEE_HISTORY_KEY: &str = "fee_history";
+const FEES_BURNED_KEY: &str = "fees_burned";
+const PAIR_INDEX_KEY: &str = "pair";
+const OWNER_INDEX_KEY: &str = "owner";
+const SOURCE_INDEX_KEY: &str = "source";
+const DERIVED_INDEX_KEY: &str = "derived";
+const OUTAGE_KEY: &str = "outage";
+const OUTAGE_COUNT_KEY: &str = "outage_count";
+const IDEMPOTENCY_KEY: &str = "idempotency";
+const SUMMARY_KEY: &str = "summary";
+// Extra time the summary outlives the subscription record to reconcile it, in ledgers (~30 days)
+const SUMMARY_TTL_GRACE: u32 = 17280 * 30;
 const TOKEN_KEY: &str = "token";
+const INIT_TOKEN_KEY: &str = "init_token";
+const TOKEN_DECIMALS_KEY: &str = "decimals";
+const DEFER_BURNS_KEY: &str = "defer_burns";
+const PENDING_BURN_KEY: &str = "pending_burn";
+const TRIGGER_INTERVAL_KEY: &str = "trigger_interval";
+const LAST_TRIGGER_KEY: &str = "last_trigger";
+const PROTOCOL_VERSION_KEY: &str = "protocol_version";
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+const FEATURES_KEY: &str = "features";
+const PAUSED_KEY: &str = "paused";
+const RETIRED_KEY: &str = "retired";
+const DEPOSITS_PAUSED_KEY: &str = "deposits_paused";
+const CHARGES_PAUSED_KEY: &str = "charges_paused";
+const TREASURY_KEY: &str = "treasury";
+const TREASURY_SHARE_KEY: &str = "treasury_share";
+const FREE_TIER_KEY: &str = "free_tier";
+const FREE_TIER_COUNT_KEY: &str = "free";
+const ASSET_TIERS_KEY: &str = "asset_tiers";
+const SOURCES_KEY: &str = "sources";
+const DEFAULTS_KEY: &str = "defaults";
 
 pub trait EnvExtensions {
     fn get_admin(&self) -> Option<Address>;
 
     fn set_admin(&self, admin: &Address);
 
+    fn get_worker(&self) -> Option<Address>;
+
+    fn set_worker(&self, worker: &Option<Address>);
+
     fn get_fee(&self) -> u64;
 
     fn set_fee(&self, base_fee: u64);
 
+    fn get_min_fee_change_interval(&self) -> u64;
+
+    fn set_min_fee_change_interval(&self, interval: u64);
+
+    fn get_last_fee_change(&self) -> Option<u64>;
+
+    fn set_last_fee_change(&self, timestamp: u64);
+
+    fn get_min_fee_floor(&self) -> u64;
+
+    fn set_min_fee_floor(&self, min_fee_floor: u64);
+
+    fn get_min_deposit(&self) -> u64;
+
+    fn set_min_deposit(&self, min_deposit: u64);
+
+    fn get_recovery_window(&self) -> Option<u64>;
+
+    fn set_recovery_window(&self, window: u64);
+
+    fn get_rounding_mode(&self) -> RoundingMode;
+
+    fn set_rounding_mode(&self, rounding_mode: RoundingMode);
+
+    fn get_webhook_prefix(&self) -> Option<Bytes>;
+
+    fn set_webhook_prefix(&self, prefix: &Option<Bytes>);
+
+    fn get_treasury(&self) -> Option<Address>;
+
+    fn set_treasury(&self, treasury: &Option<Address>);
+
+    fn get_treasury_share_bps(&self) -> u32;
+
+    fn set_treasury_share_bps(&self, share_bps: u32);
+
     fn get_token(&self) -> Address;
 
     fn set_token(&self, token: &Address);
 
+    fn get_init_token(&self) -> Option<Address>;
+
+    fn set_init_token(&self, init_token: &Option<Address>);
+
+    fn get_token_decimals(&self) -> Option<u32>;
+
+    fn set_token_decimals(&self, decimals: u32);
+
+    fn get_defer_burns(&self) -> bool;
+
+    fn set_defer_burns(&self, defer_burns: bool);
+
+    fn get_pending_burn(&self) -> u64;
+
+    fn set_pending_burn(&self, pending_burn: u64);
+
+    fn get_trigger_interval(&self) -> u64;
+
+    fn set_trigger_interval(&self, interval: u64);
+
+    fn get_last_trigger(&self) -> Option<u64>;
+
+    fn set_last_trigger(&self, timestamp: u64);
+
+    fn get_paused(&self) -> bool;
+
+    fn set_paused(&self, paused: bool);
+
+    fn is_retired(&self) -> bool;
+
+    fn set_retired(&self);
+
+    fn get_deposits_paused(&self) -> bool;
+
+    fn set_deposits_paused(&self, paused: bool);
+
+    fn get_charges_paused(&self) -> bool;
+
+    fn set_charges_paused(&self, paused: bool);
+
+    fn get_features(&self) -> Vec<Symbol>;
+
+    fn set_features(&self, features: &Vec<Symbol>);
+
+    fn get_protocol_version(&self) -> Option<u32>;
+
+    fn set_protocol_version(&self, version: u32);
+
+    fn get_schema_version(&self) -> Option<u32>;
+
+    fn set_schema_version(&self, version: u32);
+
     fn get_last_subscription_id(&self) -> u64;
 
     fn set_last_subscription_id(&self, last_subscription_id: u64);
 
+    fn get_user_id_start(&self) -> u64;
+
+    fn set_user_id_start(&self, user_id_start: u64);
+
+    fn get_balance_history_len(&self) -> u32;
+
+    fn set_balance_history_len(&self, len: u32);
+
+    fn get_subscription_count(&self) -> u64;
+
+    fn set_subscription_count(&self, count: u64);
+
+    fn get_active_count(&self) -> u64;
+
+    fn set_active_count(&self, count: u64);
+
+    fn get_total_locked(&self) -> u64;
+
+    fn set_total_locked(&self, total_locked: u64);
+
+    fn get_aggregates_rebuild(&self) -> Option<(u64, Option<u64>, u64, u64, u64)>;
+
+    fn set_aggregates_rebuild(&self, rebuild: &Option<(u64, Option<u64>, u64, u64, u64)>);
+
+    fn has_subscription(&self, subscription_id: u64) -> bool;
+
     fn get_subscription(&self, subscription_id: u64) -> Option<Subscription>;
 
     fn set_subscription(&self, subscription_id: u64, subscription: &Subscription);
 
     fn remove_subscription(&self, subscription_id: u64);
 
+    fn get_subscription_summary(&self, subscription_id: u64) -> Option<SubscriptionSummary>;
+
+    fn get_legacy_subscription(&self, subscription_id: u64) -> Option<LegacySubscription>;
+
     fn extend_subscription_ttl(&self, subscription_id: u64, extend_to: u32);
 
+    fn extend_index_entry_ttls(
+        &self,
+        subscription_id: u64,
+        pair_key: &BytesN<32>,
+        sources: &Vec<String>,
+        owner: &Address,
+        extend_to: u32,
+    );
+
+    fn get_balance_history(&self, subscription_id: u64) -> Option<Vec<(u64, u64)>>;
+
+    fn set_balance_history(&self, subscription_id: u64, history: &Vec<(u64, u64)>);
+
+    fn get_config_hash(&self, subscription_id: u64) -> Option<BytesN<32>>;
+
+    fn set_config_hash(&self, subscription_id: u64, config_hash: &Option<BytesN<32>>);
+
+    fn get_fee_history(&self) -> Vec<(u64, u64)>;
+
+    fn set_fee_history(&self, history: &Vec<(u64, u64)>);
+
+    fn get_fees_burned(&self) -> Vec<(u64, u64)>;
+
+    fn set_fees_burned(&self, buckets: &Vec<(u64, u64)>);
+
+    fn get_pair_index_page(
+        &self,
+        pair_key: &BytesN<32>,
+        start_after_id: u64,
+        limit: u32,
+    ) -> Vec<u64>;
+
+    fn add_pair_index_entry(&self, pair_key: &BytesN<32>, subscription_id: u64);
+
+    fn remove_pair_index_entry(&self, pair_key: &BytesN<32>, subscription_id: u64);
+
+    fn get_source_index_page(&self, source: &String, start_after_id: u64, limit: u32) -> Vec<u64>;
+
+    fn add_source_index_entry(&self, source: &String, subscription_id: u64);
+
+    fn remove_source_index_entry(&self, source: &String, subscription_id: u64);
+
+    fn get_outage_sources(&self) -> Vec<String>;
+
+    fn set_outage_sources(&self, sources: &Vec<String>);
+
+    fn get_outage_count(&self, source: &String) -> u32;
+
+    fn set_outage_count(&self, source: &String, count: &Option<u32>);
+
+    fn get_owner_index_page(&self, owner: &Address, start_after_id: u64, limit: u32) -> Vec<u64>;
+
+    fn add_owner_index_entry(&self, owner: &Address, subscription_id: u64);
+
+    fn remove_owner_index_entry(&self, owner: &Address, subscription_id: u64);
+
+    fn get_derived_index_page(&self, start_after_id: u64, limit: u32) -> Vec<u64>;
+
+    fn has_derived_index_entry(&self, subscription_id: u64) -> bool;
+
+    fn add_derived_index_entry(&self, subscription_id: u64);
+
+    fn remove_derived_index_entry(&self, subscription_id: u64);
+
+    fn get_idempotency_key(&self, owner: &Address, key: &BytesN<32>) -> Option<u64>;
+
+    fn set_idempotency_key(
+        &self,
+        owner: &Address,
+        key: &BytesN<32>,
+        subscription_id: u64,
+        ttl: u32,
+    );
+
+    fn get_subscription_idempotency_key(&self, subscription_id: u64) -> Option<BytesN<32>>;
+
+    fn remove_idempotency_key(&self, owner: &Address, key: &BytesN<32>, subscription_id: u64);
+
+    fn get_free_tier(&self) -> Vec<(TickerAsset, TickerAsset, u32)>;
+
+    fn set_free_tier(&self, combinations: &Vec<(TickerAsset, TickerAsset, u32)>);
+
+    fn get_defaults(&self) -> Option<(u32, ThresholdKind)>;
+
+    fn set_defaults(&self, heartbeat: u32, threshold: &ThresholdKind);
+
+    fn get_sources(&self) -> Vec<String>;
+
+    fn set_sources(&self, sources: &Vec<String>);
+
+    fn get_asset_tiers(&self) -> Vec<(TickerAsset, u32)>;
+
+    fn set_asset_tiers(&self, tiers: &Vec<(TickerAsset, u32)>);
+
+    fn get_free_tier_count(&self, owner: &Address) -> u32;
+
+    fn set_free_tier_count(&self, owner: &Address, count: u32);
+
     fn panic_if_not_admin(&self);
 
+    fn panic_if_not_admin_or_worker(&self, caller: &Address);
+
     fn is_initialized(&self) -> bool;
+
+    fn clear_config(&self);
+
+    fn clear_instance_storage(&self);
 }
 
 impl EnvExtensions for Env {
     fn is_initialized(&self) -> bool {
-        get_instance_storage(&self).has(&ADMIN_KEY)
+        // All configuration keys must be present, partially configured contract is not initialized
+        let storage = get_instance_storage(self);
+        storage.has(&ADMIN_KEY)
+            && storage.has(&TOKEN_KEY)
+            && storage.has(&BASE_FEE)
+            && storage.has(&LAST_SUBSCRIPTION_ID)
+    }
+
+    fn clear_config(&self) {
+        // Admin is removed first, the contract is considered uninitialized once any key is missing
+        let storage = get_instance_storage(self);
+        storage.remove(&ADMIN_KEY);
+        storage.remove(&BASE_FEE);
+        storage.remove(&TOKEN_KEY);
+        storage.remove(&INIT_TOKEN_KEY);
+        storage.remove(&TOKEN_DECIMALS_KEY);
+        storage.remove(&LAST_SUBSCRIPTION_ID);
+        storage.remove(&PROTOCOL_VERSION_KEY);
+        storage.remove(&FEATURES_KEY);
+        storage.remove(&WORKER_KEY);
+        storage.remove(&MIN_FEE_FLOOR_KEY);
+        storage.remove(&MIN_DEPOSIT_KEY);
+        storage.remove(&RECOVERY_WINDOW_KEY);
+        storage.remove(&ROUNDING_MODE_KEY);
+        storage.remove(&MIN_FEE_CHANGE_INTERVAL_KEY);
+        storage.remove(&LAST_FEE_CHANGE_KEY);
+        storage.remove(&WEBHOOK_PREFIX_KEY);
+        storage.remove(&SUBSCRIPTION_COUNT_KEY);
+        storage.remove(&ACTIVE_COUNT_KEY);
+        storage.remove(&TOTAL_LOCKED_KEY);
+        storage.remove(&AGGREGATES_REBUILD_KEY);
+        storage.remove(&USER_ID_START_KEY);
+        storage.remove(&BALANCE_HISTORY_LEN_KEY);
+        storage.remove(&DEFER_BURNS_KEY);
+        storage.remove(&PENDING_BURN_KEY);
+        storage.remove(&TRIGGER_INTERVAL_KEY);
+        storage.remove(&LAST_TRIGGER_KEY);
+        storage.remove(&PAUSED_KEY);
+        storage.remove(&DEPOSITS_PAUSED_KEY);
+        storage.remove(&CHARGES_PAUSED_KEY);
+        storage.remove(&TREASURY_KEY);
+        storage.remove(&TREASURY_SHARE_KEY);
+        storage.remove(&FREE_TIER_KEY);
+        storage.remove(&ASSET_TIERS_KEY);
+        storage.remove(&SOURCES_KEY);
+        storage.remove(&OUTAGE_KEY);
+        storage.remove(&DEFAULTS_KEY);
+    }
+
+    fn clear_instance_storage(&self) {
+        // Schema version describes the persistent storage layout, so it is kept by the config reset
+        self.clear_config();
+        get_instance_storage(self).remove(&SCHEMA_VERSION_KEY);
     }
 
     fn get_admin(&self) -> Option<Address> {
-        get_instance_storage(&self).get(&ADMIN_KEY)
+        get_instance_storage(self).get(&ADMIN_KEY)
     }
 
     fn set_admin(&self, admin: &Address) {
-        get_instance_storage(&self).set(&ADMIN_KEY, admin);
+        get_instance_storage(self).set(&ADMIN_KEY, admin);
+    }
+
+    fn get_worker(&self) -> Option<Address> {
+        get_instance_storage(self).get(&WORKER_KEY)
+    }
+
+    fn set_worker(&self, worker: &Option<Address>) {
+        match worker {
+            Some(worker) => get_instance_storage(self).set(&WORKER_KEY, worker),
+            None => get_instance_storage(self).remove(&WORKER_KEY),
+        }
     }
 
     fn get_fee(&self) -> u64 {
-        get_instance_storage(&self).get(&BASE_FEE).unwrap_or(0)
+        get_instance_storage(self).get(&BASE_FEE).unwrap_or(0)
     }
 
     fn set_fee(&self, base_fee: u64) {
-        get_instance_storage(&self).set(&BASE_FEE, &base_fee);
+        get_instance_storage(self).set(&BASE_FEE, &base_fee);
+    }
+
+    fn get_min_fee_change_interval(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&MIN_FEE_CHANGE_INTERVAL_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_min_fee_change_interval(&self, interval: u64) {
+        get_instance_storage(self).set(&MIN_FEE_CHANGE_INTERVAL_KEY, &interval);
+    }
+
+    fn get_last_fee_change(&self) -> Option<u64> {
+        get_instance_storage(self).get(&LAST_FEE_CHANGE_KEY)
+    }
+
+    fn set_last_fee_change(&self, timestamp: u64) {
+        get_instance_storage(self).set(&LAST_FEE_CHANGE_KEY, &timestamp);
+    }
+
+    fn get_min_fee_floor(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&MIN_FEE_FLOOR_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_min_fee_floor(&self, min_fee_floor: u64) {
+        get_instance_storage(self).set(&MIN_FEE_FLOOR_KEY, &min_fee_floor);
+    }
+
+    fn get_min_deposit(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&MIN_DEPOSIT_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_min_deposit(&self, min_deposit: u64) {
+        get_instance_storage(self).set(&MIN_DEPOSIT_KEY, &min_deposit);
+    }
+
+    fn get_recovery_window(&self) -> Option<u64> {
+        get_instance_storage(self).get(&RECOVERY_WINDOW_KEY)
+    }
+
+    fn set_recovery_window(&self, window: u64) {
+        get_instance_storage(self).set(&RECOVERY_WINDOW_KEY, &window);
+    }
+
+    fn get_rounding_mode(&self) -> RoundingMode {
+        get_instance_storage(self)
+            .get(&ROUNDING_MODE_KEY)
+            .unwrap_or(RoundingMode::Floor)
+    }
+
+    fn set_rounding_mode(&self, rounding_mode: RoundingMode) {
+        get_instance_storage(self).set(&ROUNDING_MODE_KEY, &rounding_mode);
+    }
+
+    fn get_webhook_prefix(&self) -> Option<Bytes> {
+        get_instance_storage(self).get(&WEBHOOK_PREFIX_KEY)
+    }
+
+    fn set_webhook_prefix(&self, prefix: &Option<Bytes>) {
+        match prefix {
+            Some(prefix) => get_instance_storage(self).set(&WEBHOOK_PREFIX_KEY, prefix),
+            None => get_instance_storage(self).remove(&WEBHOOK_PREFIX_KEY),
+        }
+    }
+
+    fn get_treasury(&self) -> Option<Address> {
+        get_instance_storage(self).get(&TREASURY_KEY)
+    }
+
+    fn set_treasury(&self, treasury: &Option<Address>) {
+        match treasury {
+            Some(treasury) => get_instance_storage(self).set(&TREASURY_KEY, treasury),
+            None => get_instance_storage(self).remove(&TREASURY_KEY),
+        }
+    }
+
+    fn get_treasury_share_bps(&self) -> u32 {
+        get_instance_storage(self)
+            .get(&TREASURY_SHARE_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_treasury_share_bps(&self, share_bps: u32) {
+        get_instance_storage(self).set(&TREASURY_SHARE_KEY, &share_bps);
     }
 
     fn get_token(&self) -> Address {
-        get_instance_storage(&self).get(&TOKEN_KEY).unwrap()
+        get_instance_storage(self).get(&TOKEN_KEY).unwrap()
     }
 
     fn set_token(&self, token: &Address) {
-        get_instance_storage(&self).set(&TOKEN_KEY, token);
+        get_instance_storage(self).set(&TOKEN_KEY, token);
+    }
+
+    fn get_init_token(&self) -> Option<Address> {
+        get_instance_storage(self).get(&INIT_TOKEN_KEY)
+    }
+
+    fn set_init_token(&self, init_token: &Option<Address>) {
+        match init_token {
+            Some(init_token) => get_instance_storage(self).set(&INIT_TOKEN_KEY, init_token),
+            None => get_instance_storage(self).remove(&INIT_TOKEN_KEY),
+        }
+    }
+
+    fn get_token_decimals(&self) -> Option<u32> {
+        get_instance_storage(self).get(&TOKEN_DECIMALS_KEY)
+    }
+
+    fn set_token_decimals(&self, decimals: u32) {
+        get_instance_storage(self).set(&TOKEN_DECIMALS_KEY, &decimals);
+    }
+
+    fn get_defer_burns(&self) -> bool {
+        get_instance_storage(self)
+            .get(&DEFER_BURNS_KEY)
+            .unwrap_or(false)
+    }
+
+    fn set_defer_burns(&self, defer_burns: bool) {
+        get_instance_storage(self).set(&DEFER_BURNS_KEY, &defer_burns);
+    }
+
+    fn get_pending_burn(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&PENDING_BURN_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_pending_burn(&self, pending_burn: u64) {
+        get_instance_storage(self).set(&PENDING_BURN_KEY, &pending_burn);
+    }
+
+    fn get_trigger_interval(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&TRIGGER_INTERVAL_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_trigger_interval(&self, interval: u64) {
+        get_instance_storage(self).set(&TRIGGER_INTERVAL_KEY, &interval);
+    }
+
+    fn get_last_trigger(&self) -> Option<u64> {
+        get_instance_storage(self).get(&LAST_TRIGGER_KEY)
+    }
+
+    fn set_last_trigger(&self, timestamp: u64) {
+        get_instance_storage(self).set(&LAST_TRIGGER_KEY, &timestamp);
+    }
+
+    fn get_paused(&self) -> bool {
+        get_instance_storage(self).get(&PAUSED_KEY).unwrap_or(false)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        get_instance_storage(self).set(&PAUSED_KEY, &paused);
+    }
+
+    fn is_retired(&self) -> bool {
+        get_instance_storage(self).has(&RETIRED_KEY)
+    }
+
+    fn set_retired(&self) {
+        get_instance_storage(self).set(&RETIRED_KEY, &true);
+    }
+
+    fn get_deposits_paused(&self) -> bool {
+        get_instance_storage(self)
+            .get(&DEPOSITS_PAUSED_KEY)
+            .unwrap_or(false)
+    }
+
+    fn set_deposits_paused(&self, paused: bool) {
+        get_instance_storage(self).set(&DEPOSITS_PAUSED_KEY, &paused);
+    }
+
+    fn get_charges_paused(&self) -> bool {
+        get_instance_storage(self)
+            .get(&CHARGES_PAUSED_KEY)
+            .unwrap_or(false)
+    }
+
+    fn set_charges_paused(&self, paused: bool) {
+        get_instance_storage(self).set(&CHARGES_PAUSED_KEY, &paused);
+    }
+
+    fn get_features(&self) -> Vec<Symbol> {
+        get_instance_storage(self)
+            .get(&FEATURES_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_features(&self, features: &Vec<Symbol>) {
+        get_instance_storage(self).set(&FEATURES_KEY, features);
+    }
+
+    fn get_protocol_version(&self) -> Option<u32> {
+        get_instance_storage(self).get(&PROTOCOL_VERSION_KEY)
+    }
+
+    fn set_protocol_version(&self, version: u32) {
+        get_instance_storage(self).set(&PROTOCOL_VERSION_KEY, &version);
+    }
+
+    fn get_schema_version(&self) -> Option<u32> {
+        get_instance_storage(self).get(&SCHEMA_VERSION_KEY)
+    }
+
+    fn set_schema_version(&self, version: u32) {
+        get_instance_storage(self).set(&SCHEMA_VERSION_KEY, &version);
     }
 
     fn get_last_subscription_id(&self) -> u64 {
-        get_instance_storage(&self)
+        get_instance_storage(self)
             .get(&LAST_SUBSCRIPTION_ID)
             .unwrap_or(0)
     }
 
     fn set_last_subscription_id(&self, last_subscription_id: u64) {
-        get_instance_storage(&self).set(&LAST_SUBSCRIPTION_ID, &last_subscription_id);
+        get_instance_storage(self).set(&LAST_SUBSCRIPTION_ID, &last_subscription_id);
+    }
+
+    fn get_user_id_start(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&USER_ID_START_KEY)
+            .unwrap_or(1)
+    }
+
+    fn set_user_id_start(&self, user_id_start: u64) {
+        get_instance_storage(self).set(&USER_ID_START_KEY, &user_id_start);
+    }
+
+    fn get_balance_history_len(&self) -> u32 {
+        get_instance_storage(self)
+            .get(&BALANCE_HISTORY_LEN_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_balance_history_len(&self, len: u32) {
+        get_instance_storage(self).set(&BALANCE_HISTORY_LEN_KEY, &len);
+    }
+
+    fn get_subscription_count(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&SUBSCRIPTION_COUNT_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_subscription_count(&self, count: u64) {
+        get_instance_storage(self).set(&SUBSCRIPTION_COUNT_KEY, &count);
+    }
+
+    fn get_active_count(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&ACTIVE_COUNT_KEY)
+            .unwrap_or(0)
+    }
+
+    fn set_active_count(&self, count: u64) {
+        get_instance_storage(self).set(&ACTIVE_COUNT_KEY, &count);
+    }
+
+    fn get_total_locked(&self) -> u64 {
+        get_instance_storage(self)
+            .get(&TOTAL_LOCKED_KEY)
+            .unwrap_or(0)
+    }
+
+    fn get_aggregates_rebuild(&self) -> Option<(u64, Option<u64>, u64, u64, u64)> {
+        get_instance_storage(self).get(&AGGREGATES_REBUILD_KEY)
+    }
+
+    fn set_aggregates_rebuild(&self, rebuild: &Option<(u64, Option<u64>, u64, u64, u64)>) {
+        match rebuild {
+            Some(rebuild) => get_instance_storage(self).set(&AGGREGATES_REBUILD_KEY, rebuild),
+            None => get_instance_storage(self).remove(&AGGREGATES_REBUILD_KEY),
+        }
+    }
+
+    fn set_total_locked(&self, total_locked: u64) {
+        get_instance_storage(self).set(&TOTAL_LOCKED_KEY, &total_locked);
+    }
+
+    fn has_subscription(&self, subscription_id: u64) -> bool {
+        get_persistent_storage(self).has(&subscription_id)
     }
 
     fn get_subscription(&self, subscription_id: u64) -> Option<Subscription> {
-        get_persistent_storage(&self).get(&subscription_id)
+        get_persistent_storage(self).get(&subscription_id)
     }
 
     fn set_subscription(&self, subscription_id: u64, subscription: &Subscription) {
-        get_persistent_storage(&self).set(&subscription_id, subscription);
+        get_persistent_storage(self).set(&subscription_id, subscription);
+        // Summary is rewritten only when the mirrored fields change
+        let summary_key = (SUMMARY_KEY, subscription_id);
+        let summary = SubscriptionSummary {
+            owner: subscription.owner.clone(),
+            base: subscription.base.clone(),
+            quote: subscription.quote.clone(),
+            status: subscription.status,
+            balance: subscription.balance,
+            free_tier: subscription.free_tier,
+        };
+        let stored_summary: Option<SubscriptionSummary> =
+            get_persistent_storage(self).get(&summary_key);
+        if stored_summary.as_ref() != Some(&summary) {
+            get_persistent_storage(self).set(&summary_key, &summary);
+        }
     }
 
     fn remove_subscription(&self, subscription_id: u64) {
-        get_persistent_storage(&self).remove(&subscription_id);
+        get_persistent_storage(self).remove(&subscription_id);
+        get_persistent_storage(self).remove(&(BALANCE_HISTORY_KEY, subscription_id));
+        get_persistent_storage(self).remove(&(CONFIG_HASH_KEY, subscription_id));
+        get_persistent_storage(self).remove(&(SUMMARY_KEY, subscription_id));
+    }
+
+    fn get_subscription_summary(&self, subscription_id: u64) -> Option<SubscriptionSummary> {
+        get_persistent_storage(self).get(&(SUMMARY_KEY, subscription_id))
+    }
+
+    fn get_legacy_subscription(&self, subscription_id: u64) -> Option<LegacySubscription> {
+        let record: Val = get_persistent_storage(self).get(&subscription_id)?;
+        // Legacy records lack the fields introduced with the current layout
+        let fields = Map::<Symbol, Val>::try_from_val(self, &record).ok()?;
+        if fields.contains_key(Symbol::new(self, "last_charged")) {
+            return None;
+        }
+        LegacySubscription::try_from_val(self, &record).ok()
     }
 
     fn extend_subscription_ttl(&self, subscription_id: u64, extend_to: u32) {
-        get_persistent_storage(&self).extend_ttl(&subscription_id, extend_to, extend_to)
+        get_persistent_storage(self).extend_ttl(&subscription_id, extend_to, extend_to);
+        // Summary outlives the record by the grace period to reconcile it once the record expires
+        let summary_key = (SUMMARY_KEY, subscription_id);
+        if get_persistent_storage(self).has(&summary_key) {
+            let summary_ttl = extend_to
+                .saturating_add(SUMMARY_TTL_GRACE)
+                .min(self.storage().max_ttl());
+            get_persistent_storage(self).extend_ttl(&summary_key, summary_ttl, summary_ttl);
+        }
+        // Balance history lives as long as the subscription itself
+        let history_key = (BALANCE_HISTORY_KEY, subscription_id);
+        if get_persistent_storage(self).has(&history_key) {
+            get_persistent_storage(self).extend_ttl(&history_key, extend_to, extend_to);
+        }
+        // So does the config commitment
+        let config_hash_key = (CONFIG_HASH_KEY, subscription_id);
+        if get_persistent_storage(self).has(&config_hash_key) {
+            get_persistent_storage(self).extend_ttl(&config_hash_key, extend_to, extend_to);
+        }
+    }
+
+    fn extend_index_entry_ttls(
+        &self,
+        subscription_id: u64,
+        pair_key: &BytesN<32>,
+        sources: &Vec<String>,
+        owner: &Address,
+        extend_to: u32,
+    ) {
+        // Index entries outlive the record by the grace period, lapsed records are unlinked using the summary
+        let extend_to = extend_to
+            .saturating_add(SUMMARY_TTL_GRACE)
+            .min(self.storage().max_ttl());
+        extend_list_entry_ttl(
+            self,
+            (PAIR_INDEX_KEY, pair_key.clone()).into_val(self),
+            subscription_id,
+            extend_to,
+        );
+        for source in sources.iter() {
+            extend_list_entry_ttl(
+                self,
+                (SOURCE_INDEX_KEY, source).into_val(self),
+                subscription_id,
+                extend_to,
+            );
+        }
+        extend_list_entry_ttl(
+            self,
+            (OWNER_INDEX_KEY, owner.clone()).into_val(self),
+            subscription_id,
+            extend_to,
+        );
+        extend_list_entry_ttl(
+            self,
+            DERIVED_INDEX_KEY.into_val(self),
+            subscription_id,
+            extend_to,
+        );
+    }
+
+    fn get_balance_history(&self, subscription_id: u64) -> Option<Vec<(u64, u64)>> {
+        get_persistent_storage(self).get(&(BALANCE_HISTORY_KEY, subscription_id))
+    }
+
+    fn set_balance_history(&self, subscription_id: u64, history: &Vec<(u64, u64)>) {
+        get_persistent_storage(self).set(&(BALANCE_HISTORY_KEY, subscription_id), history);
+    }
+
+    fn get_config_hash(&self, subscription_id: u64) -> Option<BytesN<32>> {
+        get_persistent_storage(self).get(&(CONFIG_HASH_KEY, subscription_id))
+    }
+
+    fn set_config_hash(&self, subscription_id: u64, config_hash: &Option<BytesN<32>>) {
+        let key = (CONFIG_HASH_KEY, subscription_id);
+        match config_hash {
+            Some(config_hash) => get_persistent_storage(self).set(&key, config_hash),
+            None => get_persistent_storage(self).remove(&key),
+        }
+    }
+
+    fn get_fee_history(&self) -> Vec<(u64, u64)> {
+        get_persistent_storage(self)
+            .get(&FEE_HISTORY_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_fee_history(&self, history: &Vec<(u64, u64)>) {
+        let max_ttl = self.storage().max_ttl();
+        get_persistent_storage(self).set(&FEE_HISTORY_KEY, history);
+        get_persistent_storage(self).extend_ttl(&FEE_HISTORY_KEY, max_ttl, max_ttl);
+    }
+
+    fn get_fees_burned(&self) -> Vec<(u64, u64)> {
+        get_persistent_storage(self)
+            .get(&FEES_BURNED_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_fees_burned(&self, buckets: &Vec<(u64, u64)>) {
+        let max_ttl = self.storage().max_ttl();
+        get_persistent_storage(self).set(&FEES_BURNED_KEY, buckets);
+        get_persistent_storage(self).extend_ttl(&FEES_BURNED_KEY, max_ttl, max_ttl);
+    }
+
+    fn get_pair_index_page(
+        &self,
+        pair_key: &BytesN<32>,
+        start_after_id: u64,
+        limit: u32,
+    ) -> Vec<u64> {
+        get_list_page(
+            self,
+            (PAIR_INDEX_KEY, pair_key.clone()).into_val(self),
+            start_after_id,
+            limit,
+        )
+    }
+
+    fn add_pair_index_entry(&self, pair_key: &BytesN<32>, subscription_id: u64) {
+        push_list_entry(
+            self,
+            (PAIR_INDEX_KEY, pair_key.clone()).into_val(self),
+            subscription_id,
+        );
+    }
+
+    fn remove_pair_index_entry(&self, pair_key: &BytesN<32>, subscription_id: u64) {
+        remove_list_entry(
+            self,
+            (PAIR_INDEX_KEY, pair_key.clone()).into_val(self),
+            subscription_id,
+        );
+    }
+
+    fn get_source_index_page(&self, source: &String, start_after_id: u64, limit: u32) -> Vec<u64> {
+        get_list_page(
+            self,
+            (SOURCE_INDEX_KEY, source.clone()).into_val(self),
+            start_after_id,
+            limit,
+        )
+    }
+
+    fn add_source_index_entry(&self, source: &String, subscription_id: u64) {
+        push_list_entry(
+            self,
+            (SOURCE_INDEX_KEY, source.clone()).into_val(self),
+            subscription_id,
+        );
+    }
+
+    fn remove_source_index_entry(&self, source: &String, subscription_id: u64) {
+        remove_list_entry(
+            self,
+            (SOURCE_INDEX_KEY, source.clone()).into_val(self),
+            subscription_id,
+        );
+    }
+
+    fn get_outage_sources(&self) -> Vec<String> {
+        get_instance_storage(self)
+            .get(&OUTAGE_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_outage_sources(&self, sources: &Vec<String>) {
+        get_instance_storage(self).set(&OUTAGE_KEY, sources);
+    }
+
+    fn get_outage_count(&self, source: &String) -> u32 {
+        get_instance_storage(self)
+            .get(&(OUTAGE_COUNT_KEY, source.clone()))
+            .unwrap_or(0)
+    }
+
+    fn set_outage_count(&self, source: &String, count: &Option<u32>) {
+        let key = (OUTAGE_COUNT_KEY, source.clone());
+        match count {
+            Some(count) => get_instance_storage(self).set(&key, count),
+            None => get_instance_storage(self).remove(&key),
+        }
+    }
+
+    fn get_owner_index_page(&self, owner: &Address, start_after_id: u64, limit: u32) -> Vec<u64> {
+        get_list_page(
+            self,
+            (OWNER_INDEX_KEY, owner.clone()).into_val(self),
+            start_after_id,
+            limit,
+        )
+    }
+
+    fn add_owner_index_entry(&self, owner: &Address, subscription_id: u64) {
+        push_list_entry(
+            self,
+            (OWNER_INDEX_KEY, owner.clone()).into_val(self),
+            subscription_id,
+        );
+    }
+
+    fn remove_owner_index_entry(&self, owner: &Address, subscription_id: u64) {
+        remove_list_entry(
+            self,
+            (OWNER_INDEX_KEY, owner.clone()).into_val(self),
+            subscription_id,
+        );
+    }
+
+    fn get_derived_index_page(&self, start_after_id: u64, limit: u32) -> Vec<u64> {
+        get_list_page(
+            self,
+            DERIVED_INDEX_KEY.into_val(self),
+            start_after_id,
+            limit,
+        )
+    }
+
+    fn has_derived_index_entry(&self, subscription_id: u64) -> bool {
+        has_list_entry(self, DERIVED_INDEX_KEY.into_val(self), subscription_id)
+    }
+
+    fn add_derived_index_entry(&self, subscription_id: u64) {
+        push_list_entry(self, DERIVED_INDEX_KEY.into_val(self), subscription_id);
+    }
+
+    fn remove_derived_index_entry(&self, subscription_id: u64) {
+        remove_list_entry(self, DERIVED_INDEX_KEY.into_val(self), subscription_id);
+    }
+
+    fn get_idempotency_key(&self, owner: &Address, key: &BytesN<32>) -> Option<u64> {
+        get_temporary_storage(self).get(&(IDEMPOTENCY_KEY, owner.clone(), key.clone()))
+    }
+
+    fn set_idempotency_key(
+        &self,
+        owner: &Address,
+        key: &BytesN<32>,
+        subscription_id: u64,
+        ttl: u32,
+    ) {
+        // Keys are kept in the temporary storage and get evicted automatically after the TTL
+        let idempotency_key = (IDEMPOTENCY_KEY, owner.clone(), key.clone());
+        get_temporary_storage(self).set(&idempotency_key, &subscription_id);
+        get_temporary_storage(self).extend_ttl(&idempotency_key, ttl, ttl);
+        // Reverse mapping lets the key follow the subscription when its ID changes
+        let subscription_key = (IDEMPOTENCY_KEY, subscription_id);
+        get_temporary_storage(self).set(&subscription_key, key);
+        get_temporary_storage(self).extend_ttl(&subscription_key, ttl, ttl);
+    }
+
+    fn get_subscription_idempotency_key(&self, subscription_id: u64) -> Option<BytesN<32>> {
+        get_temporary_storage(self).get(&(IDEMPOTENCY_KEY, subscription_id))
+    }
+
+    fn remove_idempotency_key(&self, owner: &Address, key: &BytesN<32>, subscription_id: u64) {
+        get_temporary_storage(self).remove(&(IDEMPOTENCY_KEY, owner.clone(), key.clone()));
+        get_temporary_storage(self).remove(&(IDEMPOTENCY_KEY, subscription_id));
+    }
+
+    fn get_free_tier(&self) -> Vec<(TickerAsset, TickerAsset, u32)> {
+        get_instance_storage(self)
+            .get(&FREE_TIER_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_free_tier(&self, combinations: &Vec<(TickerAsset, TickerAsset, u32)>) {
+        get_instance_storage(self).set(&FREE_TIER_KEY, combinations);
+    }
+
+    fn get_defaults(&self) -> Option<(u32, ThresholdKind)> {
+        get_instance_storage(self).get(&DEFAULTS_KEY)
+    }
+
+    fn set_defaults(&self, heartbeat: u32, threshold: &ThresholdKind) {
+        get_instance_storage(self).set(&DEFAULTS_KEY, &(heartbeat, threshold.clone()));
+    }
+
+    fn get_sources(&self) -> Vec<String> {
+        get_instance_storage(self)
+            .get(&SOURCES_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_sources(&self, sources: &Vec<String>) {
+        get_instance_storage(self).set(&SOURCES_KEY, sources);
+    }
+
+    fn get_asset_tiers(&self) -> Vec<(TickerAsset, u32)> {
+        get_instance_storage(self)
+            .get(&ASSET_TIERS_KEY)
+            .unwrap_or_else(|| Vec::new(self))
+    }
+
+    fn set_asset_tiers(&self, tiers: &Vec<(TickerAsset, u32)>) {
+        get_instance_storage(self).set(&ASSET_TIERS_KEY, tiers);
+    }
+
+    fn get_free_tier_count(&self, owner: &Address) -> u32 {
+        get_persistent_storage(self)
+            .get(&(FREE_TIER_COUNT_KEY, owner.clone()))
+            .unwrap_or(0)
+    }
+
+    fn set_free_tier_count(&self, owner: &Address, count: u32) {
+        let key = (FREE_TIER_COUNT_KEY, owner.clone());
+        if count == 0 {
+            get_persistent_storage(self).remove(&key);
+            return;
+        }
+        let max_ttl = self.storage().max_ttl();
+        get_persistent_storage(self).set(&key, &count);
+        get_persistent_storage(self).extend_ttl(&key, max_ttl, max_ttl);
     }
 
     fn panic_if_not_admin(&self) {
@@ -102,6 +1062,15 @@ impl EnvExtensions for Env {
         }
         admin.unwrap().require_auth()
     }
+
+    fn panic_if_not_admin_or_worker(&self, caller: &Address) {
+        caller.require_auth();
+        let is_admin = self.get_admin().as_ref() == Some(caller);
+        let is_worker = self.get_worker().as_ref() == Some(caller);
+        if !is_admin && !is_worker {
+            panic_with_error!(self, Error::Unauthorized);
+        }
+    }
 }
 
 fn get_instance_storage(e: &Env) -> Instance {
@@ -111,3 +1080,95 @@ fn get_instance_storage(e: &Env) -> Instance {
 fn get_persistent_storage(e: &Env) -> Persistent {
     e.storage().persistent()
 }
+
+fn get_temporary_storage(e: &Env) -> Temporary {
+    e.storage().temporary()
+}
+
+// Subscription ID lists are stored as doubly linked lists with a separate persistent entry per ID,
+// so adding, removing and paging never loads the whole list
+// The list entry holds the first and the last ID, every ID entry holds its previous and next IDs, 0 stands for none
+fn get_list_page(e: &Env, key: Val, start_after_id: u64, limit: u32) -> Vec<u64> {
+    let storage = get_persistent_storage(e);
+    let mut next = if start_after_id == 0 {
+        storage
+            .get::<Val, (u64, u64)>(&key)
+            .map_or(0, |(first, _)| first)
+    } else {
+        storage
+            .get::<(Val, u64), (u64, u64)>(&(key, start_after_id))
+            .map_or(0, |(_, next)| next)
+    };
+    let mut page = Vec::new(e);
+    while next != 0 && page.len() < limit {
+        page.push_back(next);
+        next = storage
+            .get::<(Val, u64), (u64, u64)>(&(key, next))
+            .map_or(0, |(_, next)| next);
+    }
+    page
+}
+
+fn has_list_entry(e: &Env, key: Val, id: u64) -> bool {
+    get_persistent_storage(e).has(&(key, id))
+}
+
+fn push_list_entry(e: &Env, key: Val, id: u64) {
+    let storage = get_persistent_storage(e);
+    if storage.has(&(key, id)) {
+        return;
+    }
+    let (first, last) = storage.get::<Val, (u64, u64)>(&key).unwrap_or((0, 0));
+    if last != 0 {
+        let (prev, _) = storage.get::<(Val, u64), (u64, u64)>(&(key, last)).unwrap();
+        set_list_value(e, &(key, last), (prev, id));
+    }
+    set_list_value(e, &(key, id), (last, 0));
+    set_list_value(e, &key, (if first == 0 { id } else { first }, id));
+}
+
+fn remove_list_entry(e: &Env, key: Val, id: u64) {
+    let storage = get_persistent_storage(e);
+    let (prev, next) = match storage.get::<(Val, u64), (u64, u64)>(&(key, id)) {
+        Some(entry) => entry,
+        None => return,
+    };
+    storage.remove(&(key, id));
+    let (mut first, mut last) = storage.get::<Val, (u64, u64)>(&key).unwrap_or((0, 0));
+    if prev == 0 {
+        first = next;
+    } else {
+        let (prev_prev, _) = storage.get::<(Val, u64), (u64, u64)>(&(key, prev)).unwrap();
+        set_list_value(e, &(key, prev), (prev_prev, next));
+    }
+    if next == 0 {
+        last = prev;
+    } else {
+        let (_, next_next) = storage.get::<(Val, u64), (u64, u64)>(&(key, next)).unwrap();
+        set_list_value(e, &(key, next), (prev, next_next));
+    }
+    if first == 0 {
+        storage.remove(&key);
+    } else {
+        set_list_value(e, &key, (first, last));
+    }
+}
+
+// Extend TTLs of the ID entry and the list entry, lists without the ID are left untouched
+fn extend_list_entry_ttl(e: &Env, key: Val, id: u64, extend_to: u32) {
+    let storage = get_persistent_storage(e);
+    if !storage.has(&(key, id)) {
+        return;
+    }
+    storage.extend_ttl(&(key, id), extend_to, extend_to);
+    storage.extend_ttl(&key, extend_to, extend_to);
+}
+
+fn set_list_value<K>(e: &Env, key: &K, value: (u64, u64))
+where
+    K: IntoVal<Env, Val>,
+{
+    let max_ttl = e.storage().max_ttl();
+    get_persistent_storage(e).set(key, &value);
+    get_persistent_storage(e).extend_ttl(key, max_ttl, max_ttl);
+}
@@ -5,13 +5,18 @@ mod types;
 
 use extensions::{env_extensions::EnvExtensions, u128_extensions::U128Extensions};
 use soroban_sdk::{
-    contract, contractimpl, panic_with_error, symbol_short, token::TokenClient, Address, BytesN,
-    Env, IntoVal, Symbol, Val, Vec,
+    contract, contractimpl, panic_with_error, symbol_short,
+    token::TokenClient,
+    xdr::{FromXdr, ToXdr},
+    Address, Bytes, BytesN, Env, IntoVal, String, Symbol, Val, Vec,
 };
 use types::{
-    contract_config::ContractConfig, error::Error, subscription::Subscription,
-    subscription_init_params::SubscriptionInitParams, subscription_status::SubscriptionStatus,
-    ticker_asset::TickerAsset,
+    charge_result::ChargeResult, contract_config::ContractConfig, error::Error,
+    full_config::FullConfig, heartbeat_unit::HeartbeatUnit,
+    legacy_subscription::LegacySubscription, rounding_mode::RoundingMode,
+    subscription::Subscription, subscription_init_params::SubscriptionInitParams,
+    subscription_status::SubscriptionStatus, threshold_kind::ThresholdKind,
+    threshold_param::ThresholdParam, ticker_asset::TickerAsset,
 };
 
 const REFLECTOR: Symbol = symbol_short!("reflector");
@@ -25,6 +30,55 @@ const MAX_WEBHOOK_SIZE: u32 = 2048;
 // Minimum heartbeat in minutes
 const MIN_HEARTBEAT: u32 = 5;
 
+// Minimum heartbeat in seconds for sub-minute subscriptions
+const MIN_HEARTBEAT_SECONDS: u32 = 10;
+
+// Period after which a suspended subscription expires and can't be revived anymore
+const EXPIRATION_PERIOD: u64 = 30 * DAY;
+
+// Number of ledgers an idempotency key is remembered for (~1 day)
+const IDEMPOTENCY_KEY_TTL: u32 = 17280;
+
+// Layout version of exported subscription data, bumped on every Subscription struct change
+const SUBSCRIPTION_LAYOUT_VERSION: u32 = 12;
+
+// Current storage schema version, bumped whenever stored data requires a migration
+const SCHEMA_VERSION: u32 = 1;
+
+// Maximum number of subscriptions processed in a single batch call
+const MAX_BATCH_SIZE: u32 = 20;
+
+// Maximum number of balance snapshots kept per subscription
+const MAX_BALANCE_HISTORY: u32 = 32;
+
+// Maximum number of free tier subscriptions a single owner can hold
+const MAX_FREE_TIER_PER_OWNER: u32 = 1;
+
+// Number of minutes in a day, bounds the notification window
+const MINUTES_PER_DAY: u32 = 1440;
+
+// Maximum number of base fee changes kept in the fee history
+const MAX_FEE_HISTORY: u32 = 32;
+
+// Maximum supported notification format (0 - JSON, 1 - protobuf, 2 - compact)
+const MAX_FORMAT: u32 = 2;
+
+// Default period during which a soft-cancelled subscription can be restored
+const DEFAULT_RECOVERY_WINDOW: u64 = 7 * DAY;
+
+// Creation fee multiplier of the daily retention fee for assets without a tier
+const DEFAULT_INIT_MULTIPLIER: u32 = 2;
+
+// Maximum creation fee multiplier an asset tier can set
+const MAX_INIT_MULTIPLIER: u32 = 100;
+
+// Number of most recent daily buckets of burned fees to retain
+const MAX_FEE_BUCKETS: u64 = 90;
+
+// All-zero account and contract addresses
+const ZERO_ACCOUNT: &str = "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF";
+const ZERO_CONTRACT: &str = "CAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD2KM";
+
 #[contract]
 pub struct SubscriptionContract;
 
@@ -42,20 +96,66 @@ impl SubscriptionContract {
     // # Panics
     //
     // Panics if the contract is already initialized
+    // Panics if the token address matches the admin address or is all-zero
+    // Panics if the init token matches the retention token
     pub fn config(e: Env, config: ContractConfig) {
-        config.admin.require_auth();
+        // Fail fast without requesting auth if the contract has been initialized already
         if e.is_initialized() {
             e.panic_with_error(Error::AlreadyInitialized);
         }
+        // Retired contract can't be claimed by re-initialization
+        if e.is_retired() {
+            e.panic_with_error(Error::ContractRetired);
+        }
+        config.admin.require_auth();
+        // Reject obviously broken token addresses, re-initialization is not possible
+        panic_if_invalid_token(&e, &config.admin, &config.token);
+        if let Some(init_token) = &config.init_token {
+            panic_if_invalid_token(&e, &config.admin, init_token);
+        }
+        // Separate init token makes sense only if it differs from the retention token
+        if config.init_token.as_ref() == Some(&config.token) {
+            e.panic_with_error(Error::InvalidToken);
+        }
 
-        e.set_admin(&config.admin);
         e.set_fee(config.fee);
+        record_fee_change(&e, config.fee);
         e.set_token(&config.token);
+        // Cache token decimals for event payloads
+        e.set_token_decimals(get_token_client(&e, &config.token).decimals());
+        e.set_init_token(&config.init_token);
+        e.set_features(&config.features);
         e.set_last_subscription_id(0);
+        e.set_protocol_version(parse_major_version(env!("CARGO_PKG_VERSION")));
+        // Fresh storage is created with the current schema, storage left after a teardown keeps its version
+        if e.get_schema_version().is_none() {
+            e.set_schema_version(SCHEMA_VERSION);
+        }
+        // Admin is set last, the contract is considered initialized only when all keys are present
+        e.set_admin(&config.admin);
 
         publish_updated_event(&e, &symbol_short!("config"), config);
     }
 
+    // Set or remove the worker account allowed to run routine charge and trigger operations
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `worker` - Worker account address, or None to remove the worker
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_worker(e: Env, worker: Option<Address>) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_worker(&worker);
+
+        publish_updated_event(&e, &symbol_short!("worker"), worker);
+    }
+
     // Update base Reflector subscriptions fee
     // Can be invoked only by the admin account
     //
@@ -65,513 +165,4772 @@ impl SubscriptionContract {
     //
     // # Panics
     //
+    // Panics if the contract is not initialized
     // Panics if the caller doesn't match admin address
+    // Panics if the minimum interval since the previous fee change has not elapsed yet
     pub fn set_fee(e: Env, fee: u64) {
+        panic_if_not_initialized(&e);
         e.panic_if_not_admin();
+        // Reject fee changes coming sooner than the configured interval
+        let now = now(&e);
+        if let Some(last_fee_change) = e.get_last_fee_change() {
+            if now < last_fee_change.saturating_add(e.get_min_fee_change_interval()) {
+                e.panic_with_error(Error::FeeChangeTooSoon);
+            }
+        }
+        e.set_last_fee_change(now);
         e.set_fee(fee);
+        record_fee_change(&e, fee);
 
         publish_updated_event(&e, &symbol_short!("fee"), fee);
     }
 
-    // Publish subscription trigger event
+    // Update absolute minimum daily retention fee, applied on top of the calculated fee
     // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `timestamp` - Timestamp of the trigger
-    // * `trigger_hash` - Hash of the trigger data
+    // * `min_fee_floor` - Minimum daily retention fee, 0 to disable
     //
     // # Panics
     //
+    // Panics if the contract is not initialized
     // Panics if the caller doesn't match admin address
-    pub fn trigger(e: Env, timestamp: u64, trigger_hash: BytesN<32>) {
+    pub fn set_min_fee_floor(e: Env, min_fee_floor: u64) {
+        panic_if_not_initialized(&e);
         e.panic_if_not_admin();
-        // Publish triggered event with root hash of all generated notifications
-        e.events().publish(
-            (
-                REFLECTOR,
-                symbol_short!("triggers"),
-                symbol_short!("triggered"),
-            ),
-            (timestamp, trigger_hash),
-        );
+        e.set_min_fee_floor(min_fee_floor);
+
+        publish_updated_event(&e, &symbol_short!("fee_floor"), min_fee_floor);
     }
 
-    // Charge retention fees from the subscription balances
+    // Update minimum amount accepted by a deposit
     // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `subscription_ids` - List of subscription IDs to process
+    // * `min_deposit` - Minimum deposit amount, 0 to disable
     //
     // # Panics
     //
+    // Panics if the contract is not initialized
     // Panics if the caller doesn't match admin address
-    pub fn charge(e: Env, subscription_ids: Vec<u64>) {
+    pub fn set_min_deposit(e: Env, min_deposit: u64) {
+        panic_if_not_initialized(&e);
         e.panic_if_not_admin();
-        let mut total_charge: u64 = 0;
-        let now = now(&e);
-        for subscription_id in subscription_ids.iter() {
-            if let Some(mut subscription) = e.get_subscription(subscription_id) {
-                // We can charge fees for several days in case if there was an interruption in background worker charge process
-                let days_charged = now
-                    .checked_sub(subscription.updated)
-                    .unwrap()
-                    .checked_div(DAY)
-                    .unwrap();
-                if days_charged == 0 {
-                    continue;
-                }
-                let fee = calc_fee(
-                    e.get_fee(),
-                    &subscription.base,
-                    &subscription.quote,
-                    subscription.heartbeat,
-                );
-                let mut charge = days_charged.checked_mul(fee).unwrap();
-                // Do not charge more than left on the subscription balance
-                if subscription.balance < charge {
-                    charge = subscription.balance;
-                }
-                // Deduct calculated retention fees
-                subscription.balance = subscription.balance.checked_sub(charge).unwrap();
-                subscription.updated = now;
-                // Publish charged event
-                e.events().publish(
-                    (
-                        REFLECTOR,
-                        symbol_short!("triggers"),
-                        symbol_short!("charged"),
-                        subscription.owner.clone(),
-                    ),
-                    (subscription_id, charge, now),
-                );
-                // Deactivate the subscription if the balance is less than the daily retention fee
-                if subscription.balance < fee {
-                    subscription.status = SubscriptionStatus::Suspended;
-                    // Publish suspended event
-                    e.events().publish(
-                        (
-                            REFLECTOR,
-                            symbol_short!("triggers"),
-                            symbol_short!("suspended"),
-                            subscription.owner.clone(),
-                        ),
-                        (subscription_id, now),
-                    );
-                }
-                // Update subscription properties
-                e.set_subscription(subscription_id, &subscription);
-                // Sum all retention fee charges
-                total_charge = total_charge.checked_add(charge).unwrap();
-            }
-        }
-        // Burn tokens charged from all subscriptions
-        if total_charge > 0 {
-            get_token_client(&e).burn(&e.current_contract_address(), &(total_charge as i128));
-        }
+        e.set_min_deposit(min_deposit);
+
+        publish_updated_event(&e, &symbol_short!("min_dep"), min_deposit);
     }
 
-    // Update the contract source code
+    // Update the period during which a soft-cancelled subscription can be restored
     // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `admin` - Admin account address
-    // * `wasm_hash` - WASM hash of the contract source code
+    // * `window` - Recovery window, in milliseconds
     //
     // # Panics
     //
+    // Panics if the contract is not initialized
     // Panics if the caller doesn't match admin address
-    pub fn update_contract(e: Env, wasm_hash: BytesN<32>) {
+    pub fn set_recovery_window(e: Env, window: u64) {
+        panic_if_not_initialized(&e);
         e.panic_if_not_admin();
-        e.deployer().update_current_contract_wasm(wasm_hash.clone());
+        e.set_recovery_window(window);
 
-        publish_updated_event(&e, &symbol_short!("wasm"), wasm_hash);
+        publish_updated_event(&e, &symbol_short!("recovery"), window);
     }
 
-    // Public
-
-    // Create new Reflector subscription with given parameters
+    // Update the rounding policy applied to calculated retention fees
+    // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `new_subscription` - Initialization parameters
-    // * `amount` - Initial deposit amount
+    // * `rounding_mode` - Rounding mode, Floor by default
     //
-    // # Returns
+    // # Panics
     //
-    // Subscription ID
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_rounding_mode(e: Env, rounding_mode: RoundingMode) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_rounding_mode(rounding_mode);
+
+        publish_updated_event(&e, &symbol_short!("rounding"), rounding_mode);
+    }
+
+    // Set or remove the prefix every encrypted webhook must start with
+    // Helps to catch plaintext webhooks submitted by mistake
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `prefix` - Expected ciphertext prefix, or None to disable the check
     //
     // # Panics
     //
     // Panics if the contract is not initialized
-    // Panics if the amount is less than the base fee
-    // Panics if the caller doesn't match the owner address
-    // Panics if the subscription is invalid
-    // Panics if the token transfer fails
-    pub fn create_subscription(
-        e: Env,
-        new_subscription: SubscriptionInitParams,
-        amount: u64,
-    ) -> (u64, Subscription) {
+    // Panics if the caller doesn't match admin address
+    pub fn set_webhook_prefix(e: Env, prefix: Option<Bytes>) {
         panic_if_not_initialized(&e);
-        // Check the authorization
-        new_subscription.owner.require_auth();
-        // Calculate daily retention fee based on subscription params
-        let retention_fee = calc_fee(
-            e.get_fee(),
-            &new_subscription.base,
-            &new_subscription.quote,
-            new_subscription.heartbeat,
-        );
-        // Creation fee is 2 times the daily retention fee
-        let init_fee = retention_fee.checked_mul(2).unwrap();
-        // Check the amount
-        if amount < init_fee {
-            e.panic_with_error(Error::InvalidAmount);
-        }
-        // Check subscription heartbeat
-        if MIN_HEARTBEAT > new_subscription.heartbeat {
-            e.panic_with_error(Error::InvalidHeartbeat);
-        }
-        // Check threshold
-        if new_subscription.threshold == 0 || new_subscription.threshold > 10000 {
-            e.panic_with_error(Error::InvalidThreshold);
-        }
-        // Check subscription webhook size
-        if new_subscription.webhook.len() > MAX_WEBHOOK_SIZE {
-            e.panic_with_error(Error::WebhookTooLong);
-        }
-        // Transfer and burn the tokens
-        deposit(&e, &new_subscription.owner, amount);
-        burn(&e, init_fee, amount);
-        // Create subscription itself
-        let subscription_id = e.get_last_subscription_id().checked_add(1).unwrap();
-        let subscription = Subscription {
-            owner: new_subscription.owner,
-            base: new_subscription.base,
-            quote: new_subscription.quote,
-            threshold: new_subscription.threshold,
-            heartbeat: new_subscription.heartbeat,
-            webhook: new_subscription.webhook,
-            balance: amount.checked_sub(init_fee).unwrap(),
-            status: SubscriptionStatus::Active,
-            updated: now(&e), // normalize to milliseconds
-        };
-        // Store
-        e.set_subscription(subscription_id, &subscription);
-        e.set_last_subscription_id(subscription_id);
-        // Extend TTL based on the subscription retention fee and balance
-        e.extend_subscription_ttl(
-            subscription_id,
-            calc_ledgers_to_live(&e, retention_fee, subscription.balance),
-        );
-        // Publish subscription created event
-        let data = (subscription_id, subscription.clone());
-        e.events().publish(
-            (
-                REFLECTOR,
-                symbol_short!("triggers"),
-                symbol_short!("created"),
-                subscription.owner,
-            ),
-            data.clone(),
-        );
-        return data;
+        e.panic_if_not_admin();
+        e.set_webhook_prefix(&prefix);
+
+        publish_updated_event(&e, &symbol_short!("wh_prefix"), prefix);
     }
 
-    // Deposit Reflector tokens to subscription balance
+    // Set the treasury that receives a share of collected fees instead of burning them
+    // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `from` - Account to transfer tokens from
-    // * `subscription_id` -  Subscription ID to top up
-    // * `amount` - Amount of tokens to deposit
+    // * `treasury` - Treasury address, or None to burn all fees
+    // * `share_bps` - Share of fees transferred to the treasury, in basis points
     //
     // # Panics
     //
     // Panics if the contract is not initialized
-    // Panics if the amount is zero
-    // Panics if the subscription does not exist
-    // Panics if the token transfer fails
-    pub fn deposit(e: Env, from: Address, subscription_id: u64, amount: u64) {
+    // Panics if the caller doesn't match admin address
+    // Panics if the share exceeds 10000 basis points
+    pub fn set_treasury(e: Env, treasury: Option<Address>, share_bps: u32) {
         panic_if_not_initialized(&e);
-        from.require_auth();
-        // Check deposit amount
-        if amount == 0 {
+        e.panic_if_not_admin();
+        if share_bps > 10000 {
             e.panic_with_error(Error::InvalidAmount);
         }
-        // Load subscription
-        let mut subscription = e
-            .get_subscription(subscription_id)
-            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
-        // Calculate daily retention fee based on subscription params
-        let retention_fee = calc_fee(
-            e.get_fee(),
-            &subscription.base,
-            &subscription.quote,
-            subscription.heartbeat,
-        );
-        // Transfer tokens
-        deposit(&e, &from, amount);
-        // Update subscription balance
-        subscription.balance = subscription.balance.checked_add(amount).unwrap();
-        // Update subscription status if it was suspended
-        match subscription.status {
-            SubscriptionStatus::Suspended => {
-                // Burn tokens as a revival fee
-                burn(&e, retention_fee, amount);
-                subscription.balance = subscription.balance.checked_sub(retention_fee).unwrap();
-                // Re-activate saubscription
-                subscription.status = SubscriptionStatus::Active;
-            }
-            _ => {}
-        }
-        // Update state
-        e.set_subscription(subscription_id, &subscription);
-        // Extend TTL based on the subscription retention fee and balance
-        e.extend_subscription_ttl(
-            subscription_id,
-            calc_ledgers_to_live(&e, retention_fee, subscription.balance),
-        );
-        // Publish subscription deposited event
-        e.events().publish(
-            (
-                REFLECTOR,
-                symbol_short!("triggers"),
-                symbol_short!("deposited"),
-                subscription.owner.clone(),
-            ),
-            (subscription_id, subscription, amount),
-        );
+        e.set_treasury(&treasury);
+        e.set_treasury_share_bps(share_bps);
+
+        publish_updated_event(&e, &symbol_short!("treasury"), (treasury, share_bps));
     }
 
-    // Cancel active subscription and reimburse the balance to subscription owner account
+    // Replace the list of zero-fee asset pair and heartbeat combinations
+    // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `subscription_id` - Subscription ID
+    // * `combinations` - List of base, quote and heartbeat (in minutes) combinations
     //
     // # Panics
     //
     // Panics if the contract is not initialized
-    // Panics if the subscription does not exist
-    // Panics if the caller doesn't match the owner address
-    // Panics if the subscription is not active
-    // Panics if the token transfer fails
-    pub fn cancel(e: Env, subscription_id: u64) {
+    // Panics if the caller doesn't match admin address
+    // Panics if the list size exceeds the limit
+    pub fn set_free_tier(e: Env, combinations: Vec<(TickerAsset, TickerAsset, u32)>) {
         panic_if_not_initialized(&e);
-        // Load subscription
-        let subscription = e
-            .get_subscription(subscription_id)
-            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
-        // Only owner can cancel the subscription
-        subscription.owner.require_auth();
-        match subscription.status {
-            SubscriptionStatus::Active => {}
-            _ => {
-                // Panic if the subscription is not active at the moment
-                e.panic_with_error(Error::InvalidSubscriptionStatusError);
-            }
-        }
-        // Transfer the remaining balance to the owner account
-        withdraw(&e, &subscription.owner, subscription.balance);
-        // Remove subscription from the state
-        e.remove_subscription(subscription_id);
-        // Publish subscription cancelled event
-        e.events().publish(
-            (
-                REFLECTOR,
-                symbol_short!("triggers"),
-                symbol_short!("cancelled"),
-                subscription.owner,
-            ),
-            subscription_id,
-        );
+        e.panic_if_not_admin();
+        panic_if_too_many_items(&e, combinations.len());
+        e.set_free_tier(&combinations);
+
+        publish_updated_event(&e, &symbol_short!("free_tier"), combinations);
     }
 
-    // Get subscription by ID
+    // Set the heartbeat and threshold applied to new subscriptions that don't specify them
+    // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `subscription_id` - Unique subscription ID
-    //
-    // # Returns
-    //
-    // Subscription data
+    // * `heartbeat` - Default heartbeat, in minutes
+    // * `threshold` - Default price movement threshold
     //
     // # Panics
     //
     // Panics if the contract is not initialized
-    // Panics if the subscription is not found
-    pub fn get_subscription(e: Env, subscription_id: u64) -> Subscription {
+    // Panics if the caller doesn't match admin address
+    // Panics if the heartbeat or threshold is invalid
+    pub fn set_defaults(e: Env, heartbeat: u32, threshold: ThresholdKind) {
         panic_if_not_initialized(&e);
-        // Load subscription
-        e.get_subscription(subscription_id)
-            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound))
+        e.panic_if_not_admin();
+        if !is_valid_heartbeat(heartbeat, HeartbeatUnit::Minutes) {
+            e.panic_with_error(Error::InvalidHeartbeat);
+        }
+        if !is_valid_threshold(&threshold) {
+            e.panic_with_error(Error::InvalidThreshold);
+        }
+        e.set_defaults(heartbeat, &threshold);
+
+        publish_updated_event(&e, &symbol_short!("defaults"), (heartbeat, threshold));
     }
 
-    // Calculate daily retention fee for a given subscription
+    // Add a price source to the allowlist, subscriptions can reference only allowlisted sources once it is not empty
+    // Can be invoked only by the admin account
     //
     // # Arguments
     //
-    // * `subscription_id` - Subscription ID
-    //
-    // # Returns
-    //
-    // Daily retention fees
+    // * `source` - Price source name
     //
     // # Panics
     //
     // Panics if the contract is not initialized
-    // Panics if the subscription is not found
-    pub fn get_retention_fee(e: Env, subscription_id: u64) -> u64 {
+    // Panics if the caller doesn't match admin address
+    // Panics if the list size exceeds the limit
+    pub fn add_source(e: Env, source: String) {
         panic_if_not_initialized(&e);
-        // Load subscription
-        let subscription = e
-            .get_subscription(subscription_id)
-            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
-        // Calculate daily retention fee based on subscription params
-        calc_fee(
-            e.get_fee(),
-            &subscription.base,
-            &subscription.quote,
-            subscription.heartbeat,
-        )
+        e.panic_if_not_admin();
+        let mut sources = e.get_sources();
+        if !sources.contains(&source) {
+            sources.push_back(source);
+            panic_if_too_many_items(&e, sources.len());
+            e.set_sources(&sources);
+        }
+
+        publish_updated_event(&e, &symbol_short!("sources"), sources);
     }
 
-    // Get the last subscription ID
+    // Remove a price source from the allowlist, removing the last source disables the check
+    // Can be invoked only by the admin account
     //
-    // # Returns
+    // # Arguments
     //
-    // Last subscription ID
+    // * `source` - Price source name
     //
     // # Panics
     //
     // Panics if the contract is not initialized
-    pub fn last_id(e: Env) -> u64 {
+    // Panics if the caller doesn't match admin address
+    pub fn remove_source(e: Env, source: String) {
         panic_if_not_initialized(&e);
-        // Retrieve the last value from the subscription ID counter
-        e.get_last_subscription_id()
+        e.panic_if_not_admin();
+        let mut sources = e.get_sources();
+        if let Some(index) = sources.first_index_of(&source) {
+            sources.remove(index);
+            e.set_sources(&sources);
+        }
+
+        publish_updated_event(&e, &symbol_short!("sources"), sources);
     }
 
-    // Get contract admin address
+    // Replace the table of assets with a custom creation fee multiplier
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `tiers` - List of assets with the creation fee multiplier of the daily retention fee
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the list size exceeds the limit
+    // Panics if any multiplier is zero or exceeds the limit
+    pub fn set_asset_tiers(e: Env, tiers: Vec<(TickerAsset, u32)>) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        panic_if_too_many_items(&e, tiers.len());
+        for (_, multiplier) in tiers.iter() {
+            if multiplier == 0 || multiplier > MAX_INIT_MULTIPLIER {
+                e.panic_with_error(Error::InvalidAmount);
+            }
+        }
+        e.set_asset_tiers(&tiers);
+
+        publish_updated_event(&e, &symbol_short!("tiers"), tiers);
+    }
+
+    // Set the number of balance snapshots kept per subscription, 0 disables the history
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `len` - Maximum number of snapshots
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the length exceeds the limit
+    pub fn set_balance_history_len(e: Env, len: u32) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        if len > MAX_BALANCE_HISTORY {
+            e.panic_with_error(Error::TooManyItems);
+        }
+        e.set_balance_history_len(len);
+
+        publish_updated_event(&e, &symbol_short!("hist_len"), len);
+    }
+
+    // Set the first subscription ID allocated to user subscriptions
+    // IDs below it are reserved for system subscriptions created by the admin
+    // Can be invoked only by the admin account before any subscription has been created
+    //
+    // # Arguments
+    //
+    // * `user_id_start` - First user subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the start ID is zero or subscriptions have been created already
+    pub fn set_user_id_start(e: Env, user_id_start: u64) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        if user_id_start == 0
+            || e.get_last_subscription_id() != 0
+            || e.get_subscription_count() != 0
+        {
+            e.panic_with_error(Error::InvalidIdRange);
+        }
+        e.set_user_id_start(user_id_start);
+
+        publish_updated_event(&e, &symbol_short!("id_start"), user_id_start);
+    }
+
+    // Create a system subscription with an ID from the reserved range
+    // System subscriptions are not charged and don't require a balance
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID below the user ID range start
+    // * `new_subscription` - Subscription parameters
+    //
+    // # Returns
+    //
+    // Subscription ID and subscription data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the ID is outside of the reserved range
+    // Panics if the subscription with this ID exists already
+    // Panics if the subscription parameters are invalid
+    pub fn admin_create_subscription(
+        e: Env,
+        subscription_id: u64,
+        new_subscription: SubscriptionInitParams,
+    ) -> (u64, Subscription) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        if subscription_id == 0 || subscription_id >= e.get_user_id_start() {
+            e.panic_with_error(Error::InvalidIdRange);
+        }
+        if e.has_subscription(subscription_id) {
+            e.panic_with_error(Error::IdCollision);
+        }
+        panic_if_invalid_params(&e, &new_subscription);
+        let subscription = init_subscription(&e, new_subscription, 0);
+        // System subscriptions are not charged, so keep them alive as long as possible
+        store_new_subscription(&e, subscription_id, subscription, e.storage().max_ttl())
+    }
+
+    // Publish subscription trigger event
+    // Can be invoked only by the admin or worker account
+    //
+    // # Arguments
+    //
+    // * `caller` - Admin or worker account address
+    // * `timestamp` - Timestamp of the trigger
+    // * `trigger_hash` - Hash of the trigger data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin or worker address
+    // Panics if the minimum interval since the previous trigger has not elapsed yet
+    pub fn trigger(e: Env, caller: Address, timestamp: u64, trigger_hash: BytesN<32>) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin_or_worker(&caller);
+        // Reject triggers coming sooner than the configured interval
+        let now = now(&e);
+        if let Some(last_trigger) = e.get_last_trigger() {
+            if now < last_trigger.saturating_add(e.get_trigger_interval()) {
+                e.panic_with_error(Error::TriggerTooSoon);
+            }
+        }
+        e.set_last_trigger(now);
+        // Publish triggered event with root hash of all generated notifications
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("triggered"),
+            ),
+            (timestamp, trigger_hash),
+        );
+    }
+
+    // Update contract protocol version, used after contract upgrades
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `version` - New protocol version
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_protocol_version(e: Env, version: u32) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_protocol_version(version);
+
+        publish_updated_event(&e, &symbol_short!("version"), version);
+    }
+
+    // Migrate the contract storage to the current schema version after an upgrade, one page of subscription IDs at a time
+    // Subscription records stored in the legacy layout are rewritten and added to the aggregates and indexes
+    // The rest of the contract is unavailable until the migration completes
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `start_after_id` - Last ID of the previous page, 0 to start from the beginning
+    // * `limit` - Maximum number of IDs to scan (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // Last scanned subscription ID to be passed as the cursor for the next page, 0 once the migration is complete
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn migrate(e: Env, start_after_id: u64, limit: u32) -> u64 {
+        panic_if_not_configured(&e);
+        e.panic_if_not_admin();
+        let version = e.get_schema_version().unwrap_or(0);
+        if version >= SCHEMA_VERSION {
+            return 0;
+        }
+        // Records are rewritten only once, so repeated pages don't count subscriptions twice
+        let last_id = e.get_last_subscription_id();
+        let limit = limit.clamp(1, MAX_BATCH_SIZE) as u64;
+        let mut subscription_id = start_after_id;
+        while subscription_id < last_id && subscription_id < start_after_id.saturating_add(limit) {
+            subscription_id += 1;
+            if let Some(legacy) = e.get_legacy_subscription(subscription_id) {
+                let subscription = upgrade_legacy_subscription(legacy);
+                e.set_subscription(subscription_id, &subscription);
+                extend_subscription_storage_ttl(
+                    &e,
+                    subscription_id,
+                    &subscription,
+                    calc_subscription_ttl(&e, &subscription),
+                );
+                register_migrated_subscription(&e, subscription_id, &subscription);
+            }
+        }
+        if subscription_id < last_id {
+            return subscription_id;
+        }
+        e.set_schema_version(SCHEMA_VERSION);
+
+        publish_updated_event(&e, &symbol_short!("schema"), SCHEMA_VERSION);
+        0
+    }
+
+    // Update minimum interval between base fee changes
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `interval` - Minimum interval, in milliseconds
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_min_fee_change_interval(e: Env, interval: u64) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_min_fee_change_interval(interval);
+
+        publish_updated_event(&e, &symbol_short!("fee_int"), interval);
+    }
+
+    // Update minimum interval between triggers
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `interval` - Minimum interval, in milliseconds
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_trigger_interval(e: Env, interval: u64) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_trigger_interval(interval);
+
+        publish_updated_event(&e, &symbol_short!("trig_int"), interval);
+    }
+
+    // Publish per-subscription trigger events with individual notification leaf hashes
+    // Can be invoked only by the admin or worker account
+    //
+    // # Arguments
+    //
+    // * `caller` - Admin or worker account address
+    // * `timestamp` - Timestamp of the trigger
+    // * `leaves` - List of subscription IDs with corresponding notification leaf hashes
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin or worker address
+    // Panics if the batch size exceeds the limit
+    pub fn trigger_subscriptions(
+        e: Env,
+        caller: Address,
+        timestamp: u64,
+        leaves: Vec<(u64, BytesN<32>)>,
+    ) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin_or_worker(&caller);
+        panic_if_too_many_items(&e, leaves.len());
+        for (subscription_id, leaf_hash) in leaves.iter() {
+            if let Some(mut subscription) = e.get_subscription(subscription_id) {
+                subscription.trigger_count = subscription.trigger_count.checked_add(1).unwrap();
+                subscription.last_triggered = Some(timestamp);
+                e.set_subscription(subscription_id, &subscription);
+                // Publish triggered event for the subscription owner
+                e.events().publish(
+                    (
+                        REFLECTOR,
+                        symbol_short!("triggers"),
+                        symbol_short!("triggered"),
+                        subscription.owner,
+                    ),
+                    (subscription_id, timestamp, leaf_hash),
+                );
+            }
+        }
+    }
+
+    // Charge retention fees from the subscription balances
+    // Only active subscriptions are charged, suspended subscriptions are not charged
+    // Suspended subscriptions past the expiration period are marked expired with a single `expired` event
+    // Subscriptions opted into auto-cancel are cancelled with a refund instead of being suspended
+    // Can be invoked only by the admin or worker account
+    //
+    // # Arguments
+    //
+    // * `caller` - Admin or worker account address
+    // * `subscription_ids` - List of subscription IDs to process
+    //
+    // # Returns
+    //
+    // Charged amounts and IDs of newly suspended subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin or worker address
+    // Panics if the batch size exceeds the limit
+    pub fn charge(e: Env, caller: Address, subscription_ids: Vec<u64>) -> ChargeResult {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin_or_worker(&caller);
+        charge_subscriptions(&e, subscription_ids, now(&e))
+    }
+
+    // Charge retention fees from the subscription balances, returning only the batch aggregates
+    // Can be invoked only by the admin or worker account
+    //
+    // # Arguments
+    //
+    // * `caller` - Admin or worker account address
+    // * `subscription_ids` - List of subscription IDs to process
+    //
+    // # Returns
+    //
+    // Total charged amount and IDs of newly suspended subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin or worker address
+    // Panics if the batch size exceeds the limit
+    pub fn charge_compact(e: Env, caller: Address, subscription_ids: Vec<u64>) -> (u64, Vec<u64>) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin_or_worker(&caller);
+        let result = charge_subscriptions(&e, subscription_ids, now(&e));
+        let mut total_charged: u64 = 0;
+        for (_, amount) in result.charges.iter() {
+            total_charged = total_charged.checked_add(amount).unwrap();
+        }
+        (total_charged, result.suspended)
+    }
+
+    // Reconcile subscriptions whose persistent records have expired
+    // Can be invoked only by the admin or worker account
+    //
+    // # Arguments
+    //
+    // * `caller` - Admin or worker account address
+    // * `subscription_ids` - List of subscription IDs to check
+    //
+    // # Returns
+    //
+    // IDs of subscriptions recorded as lapsed
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin or worker address
+    // Panics if the batch size exceeds the limit
+    pub fn reap_expired(e: Env, caller: Address, subscription_ids: Vec<u64>) -> Vec<u64> {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin_or_worker(&caller);
+        panic_if_too_many_items(&e, subscription_ids.len());
+        let mut lapsed = Vec::new(&e);
+        for subscription_id in subscription_ids.iter() {
+            if reap_if_lapsed(&e, subscription_id) {
+                lapsed.push_back(subscription_id);
+            }
+        }
+        lapsed
+    }
+
+    // Charge retention fees from the subscription balances as of the given past timestamp
+    // Used to settle a specific period during disaster recovery
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `subscription_ids` - List of subscription IDs to process
+    // * `as_of_ts` - Settlement timestamp, in milliseconds
+    //
+    // # Returns
+    //
+    // Charged amounts and IDs of newly suspended subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the batch size exceeds the limit
+    // Panics if the timestamp is in the future or precedes the last charge of any processed subscription
+    pub fn charge_as_of(e: Env, subscription_ids: Vec<u64>, as_of_ts: u64) -> ChargeResult {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        if as_of_ts > now(&e) {
+            e.panic_with_error(Error::InvalidTimestamp);
+        }
+        charge_subscriptions(&e, subscription_ids, as_of_ts)
+    }
+
+    // Enable or disable deferring of charged fee burns
+    // While enabled, charged fees are accumulated as pending burn instead of being burned right away
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `defer_burns` - Whether burns should be deferred
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_defer_burns(e: Env, defer_burns: bool) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_defer_burns(defer_burns);
+
+        publish_updated_event(&e, &symbol_short!("defer"), defer_burns);
+    }
+
+    // Burn all accumulated pending fees
+    // Can be invoked only by the admin account
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the token burn fails
+    pub fn flush_burns(e: Env) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        let pending_burn = e.get_pending_burn();
+        if pending_burn == 0 {
+            return;
+        }
+        burn(&e, &e.get_token(), pending_burn, pending_burn);
+        record_fees_burned(&e, pending_burn - calc_treasury_share(&e, pending_burn));
+        e.set_pending_burn(0);
+    }
+
+    // Update the contract source code
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `wasm_hash` - WASM hash of the contract source code
+    // * `features` - Capability flags enabled in the new contract code
+    // * `version` - Protocol version of the new contract code
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn update_contract(e: Env, wasm_hash: BytesN<32>, features: Vec<Symbol>, version: u32) {
+        // Upgrades are allowed before the migration to be able to roll back
+        panic_if_not_configured(&e);
+        e.panic_if_not_admin();
+        e.set_features(&features);
+        e.set_protocol_version(version);
+        e.deployer().update_current_contract_wasm(wasm_hash.clone());
+
+        // Report the new protocol version alongside the hash so indexers can track version transitions
+        publish_updated_event(&e, &symbol_short!("wasm"), (wasm_hash, version));
+    }
+
+    // Move a subscription record to a new ID
+    // Used to migrate existing subscriptions to a new ID scheme
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `old_id` - Current subscription ID
+    // * `new_id` - Target subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the subscription does not exist
+    // Panics if the target ID is zero or belongs to the reserved system ID range
+    // Panics if the target ID is already taken
+    pub fn remap_id(e: Env, old_id: u64, new_id: u64) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        let subscription = e
+            .get_subscription(old_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        if new_id == 0 || new_id < e.get_user_id_start() {
+            e.panic_with_error(Error::InvalidIdRange);
+        }
+        // Never overwrite an existing record
+        if e.has_subscription(new_id) {
+            e.panic_with_error(Error::IdCollision);
+        }
+        let history = e.get_balance_history(old_id);
+        let config_hash = e.get_config_hash(old_id);
+        // Move the record
+        e.remove_subscription(old_id);
+        e.set_subscription(new_id, &subscription);
+        if let Some(history) = history {
+            e.set_balance_history(new_id, &history);
+        }
+        e.set_config_hash(new_id, &config_hash);
+        extend_subscription_storage_ttl(
+            &e,
+            new_id,
+            &subscription,
+            calc_subscription_ttl(&e, &subscription),
+        );
+        // Retried creation requests have to resolve to the new ID
+        if let Some(idempotency_key) = e.get_subscription_idempotency_key(old_id) {
+            e.remove_idempotency_key(&subscription.owner, &idempotency_key, old_id);
+            e.set_idempotency_key(
+                &subscription.owner,
+                &idempotency_key,
+                new_id,
+                IDEMPOTENCY_KEY_TTL,
+            );
+        }
+        // Update indexes
+        remove_from_pair_index(&e, old_id, &subscription);
+        add_to_pair_index(&e, new_id, &subscription);
+        remove_from_source_index(&e, old_id, &subscription);
+        add_to_source_index(&e, new_id, &subscription);
+        remove_from_owner_index(&e, old_id, &subscription.owner);
+        add_to_owner_index(&e, new_id, &subscription.owner);
+        // Remapped IDs are not allocated sequentially
+        e.remove_derived_index_entry(old_id);
+        e.add_derived_index_entry(new_id);
+        // Publish subscription remapped event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("remapped"),
+                subscription.owner,
+            ),
+            (old_id, new_id),
+        );
+    }
+
+    // Pause active subscriptions depending on an oracle source that went offline, one page of the source index at a time
+    // Outage pauses are tracked separately from suspensions: they don't expire and aren't lifted by deposits
+    // Paused subscriptions are not billed until resumed with `resume_by_source`
+    // A summary event with the total count is published once the whole index is processed, per-subscription events are optional
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `source` - Oracle source name
+    // * `emit_each` - Whether to publish suspended events for every subscription
+    // * `start_after_id` - Last ID of the previous page, 0 to start from the beginning of the source index
+    // * `limit` - Maximum number of IDs to process (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // Last processed subscription ID to be passed as the cursor for the next page, 0 once the whole index is processed
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn suspend_by_source(
+        e: Env,
+        source: String,
+        emit_each: bool,
+        start_after_id: u64,
+        limit: u32,
+    ) -> u64 {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        let timestamp = now(&e);
+        // The source is marked offline with the first page
+        if start_after_id == 0 {
+            let mut outage_sources = e.get_outage_sources();
+            if !outage_sources.contains(&source) {
+                outage_sources.push_back(source.clone());
+                e.set_outage_sources(&outage_sources);
+            }
+        }
+        let limit = limit.min(MAX_BATCH_SIZE);
+        let page = e.get_source_index_page(&source, start_after_id, limit);
+        let mut suspended_count = 0u32;
+        // Count accumulated by the previous pages
+        let mut total_count = if start_after_id == 0 {
+            0
+        } else {
+            e.get_outage_count(&source)
+        };
+        for subscription_id in page.iter() {
+            let mut subscription = match e.get_subscription(subscription_id) {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+            if subscription.status != SubscriptionStatus::Active {
+                continue;
+            }
+            subscription.status = SubscriptionStatus::SourceOutage;
+            subscription.outage_at = Some(timestamp);
+            subscription.updated = timestamp;
+            e.set_subscription(subscription_id, &subscription);
+            suspended_count += 1;
+            if emit_each {
+                // Publish suspended event
+                e.events().publish(
+                    (
+                        REFLECTOR,
+                        symbol_short!("triggers"),
+                        symbol_short!("suspended"),
+                        subscription.owner,
+                    ),
+                    (subscription_id, timestamp),
+                );
+            }
+        }
+        update_totals(&e, -(suspended_count as i128), 0);
+        total_count += suspended_count;
+        let cursor = calc_page_cursor(&page, limit);
+        if cursor != 0 {
+            e.set_outage_count(&source, &Some(total_count));
+            return cursor;
+        }
+        e.set_outage_count(&source, &None);
+        // Publish mass suspension summary event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                Symbol::new(&e, "mass_suspended"),
+            ),
+            (source, total_count),
+        );
+        cursor
+    }
+
+    // Resume subscriptions paused by `suspend_by_source` once the oracle source recovers, one page of the source index at a time
+    // Billing is shifted by the outage duration, so the outage period is not charged while the time accrued before it is
+    // Subscriptions depending on another source that is still offline stay paused
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `source` - Oracle source name
+    // * `start_after_id` - Last ID of the previous page, 0 to start from the beginning of the source index
+    // * `limit` - Maximum number of IDs to process (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // Last processed subscription ID to be passed as the cursor for the next page, 0 once the whole index is processed
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn resume_by_source(e: Env, source: String, start_after_id: u64, limit: u32) -> u64 {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        let timestamp = now(&e);
+        // The source is marked online with the first page
+        let mut outage_sources = e.get_outage_sources();
+        if start_after_id == 0 {
+            if let Some(position) = outage_sources.first_index_of(&source) {
+                outage_sources.remove(position);
+                e.set_outage_sources(&outage_sources);
+            }
+        }
+        let limit = limit.min(MAX_BATCH_SIZE);
+        let page = e.get_source_index_page(&source, start_after_id, limit);
+        let mut resumed = 0;
+        for subscription_id in page.iter() {
+            let mut subscription = match e.get_subscription(subscription_id) {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+            if subscription.status != SubscriptionStatus::SourceOutage {
+                continue;
+            }
+            if calc_subscription_sources(&e, &subscription)
+                .iter()
+                .any(|source| outage_sources.contains(&source))
+            {
+                continue;
+            }
+            let outage_at = subscription.outage_at.unwrap_or(timestamp);
+            subscription.status = SubscriptionStatus::Active;
+            subscription.outage_at = None;
+            subscription.last_charged = subscription
+                .last_charged
+                .saturating_add(timestamp.saturating_sub(outage_at));
+            subscription.updated = timestamp;
+            e.set_subscription(subscription_id, &subscription);
+            resumed += 1;
+            // Publish resumed event
+            e.events().publish(
+                (
+                    REFLECTOR,
+                    symbol_short!("triggers"),
+                    symbol_short!("resumed"),
+                    subscription.owner,
+                ),
+                (subscription_id, timestamp),
+            );
+        }
+        update_totals(&e, resumed, 0);
+        calc_page_cursor(&page, limit)
+    }
+
+    // Recount aggregate counters over a page of subscription IDs
+    // Partial results are accumulated until committed with finalize_aggregates
+    // Sequentially allocated IDs are scanned first, followed by the index of derived and remapped IDs
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `start_after_id` - Cursor returned by the previous page, 0 restarts the rebuild
+    // * `limit` - Maximum number of IDs to scan (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // Last scanned subscription ID to be passed as the cursor for the next page, 0 once all subscriptions are counted
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the cursor doesn't continue the rebuild in progress
+    pub fn recompute_aggregates(e: Env, start_after_id: u64, limit: u32) -> u64 {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        // Pages must be processed in order to avoid counting subscriptions twice
+        let (mut cursor, mut derived_cursor, mut count, mut active_count, mut total_locked) =
+            if start_after_id == 0 {
+                (0, None, 0, 0, 0)
+            } else {
+                e.get_aggregates_rebuild()
+                    .filter(|(cursor, _, _, _, _)| *cursor != 0 && *cursor == start_after_id)
+                    .unwrap_or_else(|| panic_with_error!(e, Error::InvalidIdRange))
+            };
+        let mut remaining = limit.clamp(1, MAX_BATCH_SIZE);
+        let mut subscription_ids = Vec::new(&e);
+        // Scan the sequential ID range first
+        if derived_cursor.is_none() {
+            let last_id = calc_next_subscription_id(&e) - 1;
+            while cursor < last_id && remaining > 0 {
+                cursor += 1;
+                remaining -= 1;
+                // Remapped records are counted with the derived index
+                if !e.has_derived_index_entry(cursor) {
+                    subscription_ids.push_back(cursor);
+                }
+            }
+            if cursor >= last_id {
+                derived_cursor = Some(0);
+            }
+        }
+        // Continue with the derived and remapped IDs
+        if let Some(start_after_id) = derived_cursor {
+            if remaining > 0 {
+                let page = e.get_derived_index_page(start_after_id, remaining);
+                subscription_ids.append(&page);
+                match calc_page_cursor(&page, remaining) {
+                    // The whole index has been processed
+                    0 => cursor = 0,
+                    last_id => {
+                        cursor = last_id;
+                        derived_cursor = Some(last_id);
+                    }
+                }
+            }
+        }
+        for subscription_id in subscription_ids.iter() {
+            if let Some(subscription) = e.get_subscription(subscription_id) {
+                count += 1;
+                if subscription.status == SubscriptionStatus::Active {
+                    active_count += 1;
+                }
+                total_locked = total_locked.checked_add(subscription.balance).unwrap();
+            }
+        }
+        e.set_aggregates_rebuild(&Some((
+            cursor,
+            derived_cursor,
+            count,
+            active_count,
+            total_locked,
+        )));
+        cursor
+    }
+
+    // Replace aggregate counters with the values recounted by recompute_aggregates
+    // Can be invoked only by the admin account
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if no rebuild is in progress or it hasn't processed all subscriptions yet
+    pub fn finalize_aggregates(e: Env) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        let (_, _, count, active_count, total_locked) = e
+            .get_aggregates_rebuild()
+            .filter(|(cursor, _, _, _, _)| *cursor == 0)
+            .unwrap_or_else(|| panic_with_error!(e, Error::AggregatesNotRecomputed));
+        e.set_subscription_count(count);
+        e.set_active_count(active_count);
+        e.set_total_locked(total_locked);
+        e.set_aggregates_rebuild(&None);
+
+        publish_updated_event(
+            &e,
+            &symbol_short!("aggregate"),
+            (count, active_count, total_locked),
+        );
+    }
+
+    // Apply a bookkeeping correction to the subscription balance
+    // Tokens are not moved, the correction has to be reconciled separately
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `delta` - Signed balance change, positive to credit and negative to debit
+    // * `reason` - Correction reason for the audit trail
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if the subscription does not exist
+    // Panics if the resulting balance is negative or overflows
+    // Panics if the resulting balance is below the reserve
+    pub fn adjust_balance(e: Env, subscription_id: u64, delta: i128, reason: Symbol) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        let balance = (subscription.balance as i128)
+            .checked_add(delta)
+            .filter(|balance| *balance >= 0 && *balance <= u64::MAX as i128)
+            .unwrap_or_else(|| panic_with_error!(e, Error::InvalidAmount));
+        // Reserve has to stay covered by the balance
+        if (balance as u64) < subscription.reserve {
+            e.panic_with_error(Error::InvalidReserve);
+        }
+        subscription.balance = balance as u64;
+        subscription.updated = now(&e);
+        update_totals(&e, 0, delta);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+        // Publish adjusted event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("adjusted"),
+                subscription.owner,
+            ),
+            (subscription_id, delta, reason, subscription.balance),
+        );
+    }
+
+    // Replace the set of enabled capability flags
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `features` - Enabled capability flags
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_features(e: Env, features: Vec<Symbol>) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_features(&features);
+
+        publish_updated_event(&e, &symbol_short!("features"), features);
+    }
+
+    // Pause or resume the contract
+    // While paused, new subscriptions can't be created
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `paused` - Whether the contract should be paused
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_paused(e: Env, paused: bool) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_paused(paused);
+
+        publish_updated_event(&e, &symbol_short!("paused"), paused);
+    }
+
+    // Pause or resume deposits independently from charges
+    // While paused, subscriptions can't be topped up and new subscriptions can't be funded
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `paused` - Whether deposits should be paused
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_deposits_paused(e: Env, paused: bool) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_deposits_paused(paused);
+
+        publish_updated_event(&e, &symbol_short!("dep_pause"), paused);
+    }
+
+    // Pause or resume retention fee charges independently from deposits
+    // Can be invoked only by the admin account
+    //
+    // # Arguments
+    //
+    // * `paused` - Whether charges should be paused
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    pub fn set_charges_paused(e: Env, paused: bool) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        e.set_charges_paused(paused);
+
+        publish_updated_event(&e, &symbol_short!("chg_pause"), paused);
+    }
+
+    // Permanently retire the contract, removing all instance storage to reclaim rent
+    // Only the retired flag is kept, so the contract can't be configured again
+    // Can be invoked only by the admin account when the contract is paused and has no subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if any subscription still exists
+    // Panics if the contract is not paused
+    // Panics if deferred burns have not been flushed
+    pub fn retire(e: Env) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        if e.get_subscription_count() > 0 {
+            e.panic_with_error(Error::HasActiveSubscriptions);
+        }
+        if !e.get_paused() {
+            e.panic_with_error(Error::ContractNotPaused);
+        }
+        // Do not strand tokens charged for burning
+        if e.get_pending_burn() > 0 {
+            e.panic_with_error(Error::PendingBurns);
+        }
+        let admin = e.get_admin().unwrap();
+        e.clear_instance_storage();
+        e.set_retired();
+
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("retired"),
+            ),
+            admin,
+        );
+    }
+
+    // Reset the contract to the uninitialized state, so it can be configured again
+    // All settings are removed, only the storage schema version is kept
+    // Can be invoked only by the admin account when there are no live subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match admin address
+    // Panics if any subscription still exists
+    // Panics if deferred burns have not been flushed
+    pub fn teardown(e: Env) {
+        panic_if_not_initialized(&e);
+        e.panic_if_not_admin();
+        // Do not strand subscription balances
+        if e.get_subscription_count() > 0 {
+            e.panic_with_error(Error::HasActiveSubscriptions);
+        }
+        // Do not strand tokens charged for burning
+        if e.get_pending_burn() > 0 {
+            e.panic_with_error(Error::PendingBurns);
+        }
+        e.clear_config();
+
+        publish_updated_event(&e, &symbol_short!("teardown"), ());
+    }
+
+    // Public
+
+    // Create new Reflector subscription with given parameters
+    //
+    // # Arguments
+    //
+    // * `new_subscription` - Initialization parameters
+    // * `amount` - Initial deposit amount
+    // * `idempotency_key` - Optional key that makes retried requests return the already created subscription
+    //
+    // # Returns
+    //
+    // Subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the amount is less than the base fee
+    // Panics if the amount doesn't cover one day of retention when the creation fee is paid in the init token
+    // Panics if the caller doesn't match the owner address
+    // Panics if the subscription is invalid
+    // Panics if the reserve exceeds the subscription balance
+    // Panics if the derived subscription ID is already taken
+    // Panics if the contract is paused
+    // Panics if the token transfer fails
+    pub fn create_subscription(
+        e: Env,
+        new_subscription: SubscriptionInitParams,
+        amount: u64,
+        idempotency_key: Option<BytesN<32>>,
+    ) -> (u64, Subscription) {
+        panic_if_not_initialized(&e);
+        // Bind the owner authorization to the exact params, amount and idempotency key
+        new_subscription.owner.require_auth_for_args(
+            (new_subscription.clone(), amount, idempotency_key.clone()).into_val(&e),
+        );
+        create_subscription(&e, new_subscription, amount, idempotency_key, true)
+    }
+
+    // Create new Reflector subscription funded for the given number of days
+    //
+    // # Arguments
+    //
+    // * `new_subscription` - Initialization parameters
+    // * `days` - Number of days the initial balance should cover after the creation fee
+    //
+    // # Returns
+    //
+    // New subscription ID and data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the number of days is 0
+    // Panics if the caller doesn't match the owner address
+    // Panics if the subscription is invalid
+    // Panics if the contract is paused
+    // Panics if the token transfer fails
+    pub fn create_subscription_for_days(
+        e: Env,
+        new_subscription: SubscriptionInitParams,
+        days: u32,
+    ) -> (u64, Subscription) {
+        panic_if_not_initialized(&e);
+        new_subscription
+            .owner
+            .require_auth_for_args((new_subscription.clone(), days).into_val(&e));
+        if days == 0 {
+            e.panic_with_error(Error::InvalidAmount);
+        }
+        let (init_cost, retention_fee) = calc_creation_costs(&e, &new_subscription);
+        let amount = retention_fee
+            .checked_mul(days as u64)
+            .unwrap()
+            .checked_add(init_cost)
+            .unwrap();
+        create_subscription(&e, new_subscription, amount, None, true)
+    }
+
+    // Create new subscription with the same params as an existing one
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - ID of the subscription to copy params from
+    // * `amount` - Initial deposit amount
+    // * `heartbeat_override` - Optional heartbeat for the new subscription, in the source subscription heartbeat units
+    //
+    // # Returns
+    //
+    // New subscription ID and data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the source subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the amount is less than the creation fee
+    // Panics if the new subscription is invalid
+    // Panics if the token transfer fails
+    pub fn clone_subscription(
+        e: Env,
+        subscription_id: u64,
+        amount: u64,
+        heartbeat_override: Option<u32>,
+    ) -> (u64, Subscription) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let source = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        source
+            .owner
+            .require_auth_for_args((subscription_id, amount, heartbeat_override).into_val(&e));
+        // Copy subscription params, balance related settings are not inherited
+        let new_subscription = SubscriptionInitParams {
+            owner: source.owner,
+            base: source.base,
+            quote: source.quote,
+            threshold: ThresholdParam::Custom(source.threshold),
+            heartbeat: Some(heartbeat_override.unwrap_or(source.heartbeat)),
+            heartbeat_unit: source.heartbeat_unit,
+            webhook: source.webhook,
+            reserve: 0,
+            id_nonce: None,
+            format: source.format,
+            expires_at: source.expires_at,
+            active_from_minute: source.active_from_minute,
+            active_to_minute: source.active_to_minute,
+        };
+        create_subscription(&e, new_subscription, amount, None, true)
+    }
+
+    // Create new subscription funded by a part of an existing subscription balance
+    // The source subscription stays active with the remaining balance
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - ID of the subscription to take the balance from
+    // * `transfer_balance` - Amount moved to the new subscription, the creation fee is taken from it
+    // * `new_params` - Initialization parameters of the new subscription
+    //
+    // # Returns
+    //
+    // New subscription ID and data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the source subscription does not exist or is not active
+    // Panics if the caller doesn't match the owner address
+    // Panics if the new subscription owner differs from the source subscription owner
+    // Panics if the source subscription is left with less than one day of runway
+    // Panics if the amount is less than the creation fee
+    // Panics if the new subscription is invalid
+    // Panics if the contract is paused
+    pub fn split_subscription(
+        e: Env,
+        subscription_id: u64,
+        transfer_balance: u64,
+        new_params: SubscriptionInitParams,
+    ) -> (u64, Subscription) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut source = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        source.owner.require_auth_for_args(
+            (subscription_id, transfer_balance, new_params.clone()).into_val(&e),
+        );
+        if new_params.owner != source.owner {
+            e.panic_with_error(Error::Unauthorized);
+        }
+        if source.status != SubscriptionStatus::Active {
+            e.panic_with_error(Error::InvalidSubscriptionStatusError);
+        }
+        let fee = calc_subscription_fee(&e, &source);
+        // Settle the days accrued by the source subscription before moving its balance
+        let timestamp = now(&e);
+        let days_charged = calc_charge_until(&source, timestamp)
+            .saturating_sub(source.last_charged)
+            .checked_div(DAY)
+            .unwrap();
+        if days_charged > 0 && fee > 0 {
+            let charge = deduct_accrued_fees(&mut source, days_charged, fee);
+            source.last_charged = timestamp;
+            burn_charged_fees(&e, charge);
+            update_totals(&e, 0, -(charge as i128));
+            record_balance(&e, subscription_id, source.balance);
+            publish_charged_event(&e, subscription_id, &source, charge, timestamp);
+        }
+        // Source subscription has to keep at least one day of runway, the reserve can't be moved
+        let chargeable = source.balance.saturating_sub(source.reserve);
+        if transfer_balance == 0
+            || transfer_balance > chargeable
+            || chargeable - transfer_balance < fee
+        {
+            e.panic_with_error(Error::InvalidAmount);
+        }
+        source.balance = source.balance.checked_sub(transfer_balance).unwrap();
+        source.updated = now(&e);
+        e.set_subscription(subscription_id, &source);
+        extend_subscription_storage_ttl(
+            &e,
+            subscription_id,
+            &source,
+            calc_subscription_ttl(&e, &source),
+        );
+        record_balance(&e, subscription_id, source.balance);
+        update_totals(&e, 0, -(transfer_balance as i128));
+        // Moved tokens are already held by the contract, retries are not deduplicated
+        let (new_id, subscription) =
+            create_subscription(&e, new_params, transfer_balance, None, false);
+        // Publish split event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("split"),
+                source.owner,
+            ),
+            (subscription_id, new_id, transfer_balance),
+        );
+        (new_id, subscription)
+    }
+
+    // Merge two subscriptions with identical params by moving the balance and reserve of one into the other
+    // Fees accrued by the merged subscription since its last charge are settled first, then it is removed
+    //
+    // # Arguments
+    //
+    // * `into_id` - ID of the active subscription receiving the balance
+    // * `from_id` - ID of the active or suspended subscription to remove
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if any of the subscriptions does not exist
+    // Panics if the subscriptions are the same or have different owners
+    // Panics if the caller doesn't match the owner address
+    // Panics if the subscription statuses don't allow merging
+    // Panics if the subscription params don't match
+    pub fn merge_subscriptions(e: Env, into_id: u64, from_id: u64) {
+        panic_if_not_initialized(&e);
+        // Load subscriptions
+        let mut into = e
+            .get_subscription(into_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        let mut from = e
+            .get_subscription(from_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        if into_id == from_id || into.owner != from.owner {
+            e.panic_with_error(Error::Unauthorized);
+        }
+        // Only owner can merge subscriptions
+        into.owner.require_auth();
+        if into.status != SubscriptionStatus::Active
+            || (from.status != SubscriptionStatus::Active
+                && from.status != SubscriptionStatus::Suspended)
+        {
+            e.panic_with_error(Error::InvalidSubscriptionStatusError);
+        }
+        if into.base != from.base
+            || into.quote != from.quote
+            || into.heartbeat != from.heartbeat
+            || into.heartbeat_unit != from.heartbeat_unit
+            || into.threshold != from.threshold
+        {
+            e.panic_with_error(Error::ParamsMismatch);
+        }
+        // Charge the days accrued by the merged subscription, the time past its last full day is not billed
+        if from.status == SubscriptionStatus::Active {
+            let timestamp = now(&e);
+            let days_charged = calc_charge_until(&from, timestamp)
+                .saturating_sub(from.last_charged)
+                .checked_div(DAY)
+                .unwrap();
+            let fee = calc_subscription_fee(&e, &from);
+            if days_charged > 0 && fee > 0 {
+                let charge = deduct_accrued_fees(&mut from, days_charged, fee);
+                from.last_charged = timestamp;
+                burn_charged_fees(&e, charge);
+                update_totals(&e, 0, -(charge as i128));
+                record_balance(&e, from_id, from.balance);
+                publish_charged_event(&e, from_id, &from, charge, timestamp);
+            }
+        }
+        // Tokens stay on the contract balance, only the ownership record moves
+        remove_subscription_record(&e, from_id, &from);
+        into.balance = into.balance.checked_add(from.balance).unwrap();
+        into.reserve = into.reserve.checked_add(from.reserve).unwrap();
+        into.updated = now(&e);
+        update_totals(&e, 0, from.balance as i128);
+        e.set_subscription(into_id, &into);
+        extend_subscription_storage_ttl(&e, into_id, &into, calc_subscription_ttl(&e, &into));
+        record_balance(&e, into_id, into.balance);
+        // Publish merged event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("merged"),
+                into.owner,
+            ),
+            (into_id, from_id, from.balance),
+        );
+    }
+
+    // Deposit Reflector tokens to subscription balance
+    //
+    // # Arguments
+    //
+    // * `from` - Account to transfer tokens from
+    // * `subscription_id` -  Subscription ID to top up
+    // * `amount` - Amount of tokens to deposit
+    // * `max_fee` - Optional maximum daily retention fee, also charged as the revival fee, the caller accepts
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the amount is zero
+    // Panics if the amount is below the minimum deposit
+    // Panics if the subscription does not exist
+    // Panics if the retention fee exceeds the maximum fee
+    // Panics if the deposit is too small to revive a suspended subscription
+    // Panics if the token transfer fails
+    pub fn deposit(e: Env, from: Address, subscription_id: u64, amount: u64, max_fee: Option<u64>) {
+        panic_if_not_initialized(&e);
+        // Bind the authorization to the exact subscription, amount and fee limit
+        from.require_auth_for_args((from.clone(), subscription_id, amount, max_fee).into_val(&e));
+        // Check deposit amount
+        if amount == 0 {
+            e.panic_with_error(Error::InvalidAmount);
+        }
+        // Reject dust deposits
+        if amount < e.get_min_deposit() {
+            e.panic_with_error(Error::DepositTooSmall);
+        }
+        let subscription = load_depositable_subscription(&e, subscription_id);
+        // Protect the caller from fee increases made after signing
+        if max_fee.is_some_and(|max_fee| calc_subscription_fee(&e, &subscription) > max_fee) {
+            e.panic_with_error(Error::FeeChangedError);
+        }
+        top_up_subscription(&e, &from, subscription_id, subscription, amount);
+    }
+
+    // Deposit Reflector tokens required to bring the subscription up to the target runway
+    // The reserve doesn't count towards the runway, suspended subscriptions are topped up with the revival fee included
+    // Shortfalls below the minimum deposit are rounded up to it
+    //
+    // # Arguments
+    //
+    // * `from` - Account to transfer tokens from
+    // * `subscription_id` -  Subscription ID to top up
+    // * `target_days` - Desired runway, in days
+    //
+    // # Returns
+    //
+    // Deposited amount, zero if the subscription balance already covers the target
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the subscription has expired
+    // Panics if the token transfer fails
+    pub fn deposit_to_runway(e: Env, from: Address, subscription_id: u64, target_days: u32) -> u64 {
+        panic_if_not_initialized(&e);
+        from.require_auth_for_args((from.clone(), subscription_id, target_days).into_val(&e));
+        let subscription = load_depositable_subscription(&e, subscription_id);
+        let retention_fee = calc_subscription_fee(&e, &subscription);
+        let mut target = retention_fee.checked_mul(target_days as u64).unwrap();
+        // Revival fee is burned from the deposit and doesn't count towards the runway, revived subscriptions need at least one day
+        if subscription.status == SubscriptionStatus::Suspended {
+            target = target
+                .max(retention_fee)
+                .checked_add(retention_fee)
+                .unwrap();
+        }
+        // Only the balance above the reserve can be charged
+        let chargeable = subscription.balance.saturating_sub(subscription.reserve);
+        let mut amount = target.saturating_sub(chargeable);
+        if amount > 0 {
+            amount = amount.max(e.get_min_deposit());
+            top_up_subscription(&e, &from, subscription_id, subscription, amount);
+        }
+        amount
+    }
+
+    // Set the subscription balance reserve that can't be charged
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `reserve` - Reserved amount
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the reserve exceeds the subscription balance
+    pub fn set_reserve(e: Env, subscription_id: u64, reserve: u64) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change the reserve
+        subscription.owner.require_auth();
+        if reserve > subscription.balance {
+            e.panic_with_error(Error::InvalidReserve);
+        }
+        subscription.reserve = reserve;
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+    }
+
+    // Set subscription event preferences
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `emit_charge_events` - Whether to publish an event for each retention fee charge
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    pub fn set_event_prefs(e: Env, subscription_id: u64, emit_charge_events: bool) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change event preferences
+        subscription.owner.require_auth();
+        subscription.emit_charge_events = emit_charge_events;
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+    }
+
+    // Set whether the subscription is cancelled instead of being suspended
+    // Cancelled subscription balance is refunded to the owner
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `auto_cancel_on_suspend` - Whether to cancel the subscription when it would be suspended
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    pub fn set_auto_cancel(e: Env, subscription_id: u64, auto_cancel_on_suspend: bool) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change the suspension preference
+        subscription.owner.require_auth();
+        subscription.auto_cancel_on_suspend = auto_cancel_on_suspend;
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+    }
+
+    // Set the maximum daily retention fee the owner is willing to pay
+    // The subscription gets suspended on charge if the current fee exceeds it
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `max_acceptable_fee` - Maximum daily retention fee, or None to remove the cap
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    pub fn set_max_acceptable_fee(e: Env, subscription_id: u64, max_acceptable_fee: Option<u64>) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change the fee cap
+        subscription.owner.require_auth();
+        subscription.max_acceptable_fee = max_acceptable_fee;
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+    }
+
+    // Set preferred notification payload format
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `format` - Notification format
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the format is not supported
+    pub fn set_format(e: Env, subscription_id: u64, format: u32) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change the format
+        subscription.owner.require_auth();
+        panic_if_invalid_format(&e, format);
+        subscription.format = format;
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+    }
+
+    // Commit to an off-chain notification config document
+    // The commitment is stored alongside the subscription record and shares its lifetime
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `config_hash` - SHA-256 hash of the config document, or None to remove the commitment
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    pub fn set_config_hash(e: Env, subscription_id: u64, config_hash: Option<BytesN<32>>) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change the config commitment
+        subscription.owner.require_auth();
+        e.set_config_hash(subscription_id, &config_hash);
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+        extend_subscription_storage_ttl(
+            &e,
+            subscription_id,
+            &subscription,
+            calc_subscription_ttl(&e, &subscription),
+        );
+    }
+
+    // Lower the subscription notification cadence to reduce the retention fee
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `new_heartbeat` - New heartbeat in the subscription heartbeat units, not lower than the current one
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the new heartbeat is lower than the current one
+    // Panics if the new heartbeat moves the subscription into the free tier and the owner limit is reached
+    pub fn reduce_cost(e: Env, subscription_id: u64, new_heartbeat: u32) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can change the heartbeat
+        subscription.owner.require_auth();
+        // Higher heartbeat can only lower the retention fee
+        if new_heartbeat < subscription.heartbeat {
+            e.panic_with_error(Error::InvalidHeartbeat);
+        }
+        // Settle the days accrued at the current heartbeat before lowering the fee
+        if subscription.status == SubscriptionStatus::Active {
+            let timestamp = now(&e);
+            let days_charged = calc_charge_until(&subscription, timestamp)
+                .saturating_sub(subscription.last_charged)
+                .checked_div(DAY)
+                .unwrap();
+            let fee = calc_subscription_fee(&e, &subscription);
+            if days_charged > 0 && fee > 0 {
+                let charge = deduct_accrued_fees(&mut subscription, days_charged, fee);
+                subscription.last_charged = timestamp;
+                burn_charged_fees(&e, charge);
+                update_totals(&e, 0, -(charge as i128));
+                record_balance(&e, subscription_id, subscription.balance);
+                publish_charged_event(&e, subscription_id, &subscription, charge, timestamp);
+            }
+        }
+        subscription.heartbeat = new_heartbeat;
+        // Zero-fee allowlist matches exact heartbeats, so the new heartbeat may move the subscription in or out of it
+        let free_tier = is_free_tier(
+            &e,
+            &subscription.base,
+            &subscription.quote,
+            calc_heartbeat_seconds(new_heartbeat, subscription.heartbeat_unit),
+        );
+        if free_tier != subscription.free_tier {
+            if free_tier {
+                increment_free_tier_count(&e, &subscription.owner);
+            } else {
+                decrement_free_tier_count(&e, &subscription.owner);
+                // Free days are not billed retroactively
+                subscription.last_charged = now(&e);
+            }
+            subscription.free_tier = free_tier;
+        }
+        subscription.updated = now(&e);
+        // Update state, lower fee makes the balance last longer
+        e.set_subscription(subscription_id, &subscription);
+        extend_subscription_storage_ttl(
+            &e,
+            subscription_id,
+            &subscription,
+            calc_subscription_ttl(&e, &subscription),
+        );
+        // Publish heartbeat changed event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("heartbeat"),
+                subscription.owner,
+            ),
+            (subscription_id, new_heartbeat),
+        );
+    }
+
+    // Propose transferring the subscription ownership to another account
+    // The transfer has to be accepted by the new owner
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `new_owner` - Proposed owner address
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    pub fn propose_transfer(e: Env, subscription_id: u64, new_owner: Address) {
+        set_pending_owner(&e, subscription_id, Some(new_owner));
+    }
+
+    // Cancel the pending ownership transfer proposal
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    pub fn cancel_transfer(e: Env, subscription_id: u64) {
+        set_pending_owner(&e, subscription_id, None);
+    }
+
+    // Accept the pending ownership transfer
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if there is no pending transfer
+    // Panics if the caller doesn't match the proposed owner address
+    pub fn accept_transfer(e: Env, subscription_id: u64) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        let new_owner = subscription
+            .pending_owner
+            .take()
+            .unwrap_or_else(|| panic_with_error!(e, Error::NoPendingTransfer));
+        // Only the proposed owner can accept the transfer
+        new_owner.require_auth();
+        let previous_owner = subscription.owner;
+        subscription.owner = new_owner.clone();
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+        remove_from_owner_index(&e, subscription_id, &previous_owner);
+        add_to_owner_index(&e, subscription_id, &new_owner);
+        if subscription.free_tier {
+            decrement_free_tier_count(&e, &previous_owner);
+            increment_free_tier_count(&e, &new_owner);
+        }
+        // Publish subscription transferred event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("transfer"),
+                new_owner,
+            ),
+            (subscription_id, previous_owner),
+        );
+    }
+
+    // Cancel subscription and reimburse the balance to subscription owner account
+    // Suspended subscriptions can be cancelled as well, so the reserve is always recoverable
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `burn_residual` - Burn the remaining balance instead of refunding it to the owner
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the token transfer fails
+    pub fn cancel(e: Env, subscription_id: u64, burn_residual: bool) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can cancel the subscription
+        subscription.owner.require_auth();
+        cancel_subscription(&e, subscription_id, subscription, burn_residual, false);
+    }
+
+    // Cancel subscription on behalf of the owner
+    // Can be submitted by any account, but requires the owner authorization
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `owner` - Subscription owner address
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the owner doesn't match the subscription owner or doesn't authorize the call
+    // Panics if the token transfer fails
+    pub fn cancel_for(e: Env, subscription_id: u64, owner: Address) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        if subscription.owner != owner {
+            e.panic_with_error(Error::Unauthorized);
+        }
+        owner.require_auth();
+        cancel_subscription(&e, subscription_id, subscription, false, false);
+    }
+
+    // Cancel subscription keeping the record and balance for the recovery window
+    // Subscription is not charged nor triggered until it gets restored or finalized
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the subscription is expired or already pending deletion
+    pub fn soft_cancel(e: Env, subscription_id: u64) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can cancel the subscription
+        subscription.owner.require_auth();
+        let active_change = match subscription.status {
+            SubscriptionStatus::Active => -1,
+            SubscriptionStatus::Suspended | SubscriptionStatus::SourceOutage => 0,
+            _ => panic_with_error!(e, Error::InvalidSubscriptionStatusError),
+        };
+        subscription.status = SubscriptionStatus::PendingDeletion;
+        subscription.cancelled_at = Some(now(&e));
+        subscription.updated = now(&e);
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+        update_totals(&e, active_change, 0);
+        // Publish soft deletion event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("soft_del"),
+                subscription.owner,
+            ),
+            (subscription_id, now(&e)),
+        );
+    }
+
+    // Restore a soft-cancelled subscription within the recovery window
+    // Previously suspended subscriptions are restored as suspended, active ones resume billing
+    // with the pending deletion period excluded from the billed time
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the caller doesn't match the owner address
+    // Panics if the subscription is not pending deletion
+    // Panics if the recovery window has passed
+    pub fn restore(e: Env, subscription_id: u64) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let mut subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Only owner can restore the subscription
+        subscription.owner.require_auth();
+        let cancelled_at = get_pending_deletion_timestamp(&e, &subscription);
+        if now(&e) >= cancelled_at.saturating_add(get_recovery_window(&e)) {
+            e.panic_with_error(Error::InvalidTimestamp);
+        }
+        subscription.cancelled_at = None;
+        subscription.updated = now(&e);
+        if subscription.suspended_at.is_some() {
+            subscription.status = SubscriptionStatus::Suspended;
+        } else if let Some(outage_at) = subscription.outage_at {
+            // Source outage pause covers the pending deletion period, it is lifted if the sources recovered meanwhile
+            let outage_sources = e.get_outage_sources();
+            if calc_subscription_sources(&e, &subscription)
+                .iter()
+                .any(|source| outage_sources.contains(&source))
+            {
+                subscription.status = SubscriptionStatus::SourceOutage;
+            } else {
+                subscription.status = SubscriptionStatus::Active;
+                subscription.outage_at = None;
+                subscription.last_charged = subscription
+                    .last_charged
+                    .saturating_add(now(&e) - outage_at);
+                update_totals(&e, 1, 0);
+            }
+        } else {
+            // The pending deletion period is not billed, time accrued before it still is
+            subscription.status = SubscriptionStatus::Active;
+            subscription.last_charged = subscription
+                .last_charged
+                .saturating_add(now(&e) - cancelled_at);
+            update_totals(&e, 1, 0);
+        }
+        // Update state
+        e.set_subscription(subscription_id, &subscription);
+        // Publish restored event
+        e.events().publish(
+            (
+                REFLECTOR,
+                symbol_short!("triggers"),
+                symbol_short!("restored"),
+                subscription.owner,
+            ),
+            subscription_id,
+        );
+    }
+
+    // Remove a soft-cancelled subscription after the recovery window and refund its balance to the owner
+    // Can be invoked by any account as a routine maintenance
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription does not exist
+    // Panics if the subscription is not pending deletion
+    // Panics if the recovery window has not passed yet
+    // Panics if the token transfer fails
+    pub fn finalize_cancel(e: Env, subscription_id: u64) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        let cancelled_at = get_pending_deletion_timestamp(&e, &subscription);
+        if now(&e) < cancelled_at.saturating_add(get_recovery_window(&e)) {
+            e.panic_with_error(Error::InvalidTimestamp);
+        }
+        cancel_subscription(&e, subscription_id, subscription, false, false);
+    }
+
+    // Extend storage TTLs of funded subscriptions according to their balances
+    // Can be invoked by any account as a routine maintenance
+    //
+    // # Arguments
+    //
+    // * `subscription_ids` - List of subscription IDs to process
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the batch size exceeds the limit
+    pub fn extend_ttls(e: Env, subscription_ids: Vec<u64>) {
+        panic_if_not_initialized(&e);
+        panic_if_too_many_items(&e, subscription_ids.len());
+        for subscription_id in subscription_ids.iter() {
+            // Missing subscriptions are skipped
+            if let Some(subscription) = e.get_subscription(subscription_id) {
+                extend_subscription_storage_ttl(
+                    &e,
+                    subscription_id,
+                    &subscription,
+                    calc_subscription_ttl(&e, &subscription),
+                );
+            }
+        }
+    }
+
+    // Export the whole subscription state as a single encoded blob
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // XDR-encoded tuple of the layout version and subscription data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn export_subscription(e: Env, subscription_id: u64) -> Bytes {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        (SUBSCRIPTION_LAYOUT_VERSION, subscription).to_xdr(&e)
+    }
+
+    // Decode subscription data produced by export_subscription
+    //
+    // # Arguments
+    //
+    // * `data` - Exported subscription data
+    //
+    // # Returns
+    //
+    // Decoded subscription
+    //
+    // # Panics
+    //
+    // Panics if the data is malformed or has been exported with a different layout version
+    pub fn decode_subscription(e: Env, data: Bytes) -> Subscription {
+        match <(u32, Subscription)>::from_xdr(&e, &data) {
+            Ok((SUBSCRIPTION_LAYOUT_VERSION, subscription)) => subscription,
+            _ => panic_with_error!(e, Error::InvalidSubscriptionData),
+        }
+    }
+
+    // Estimate the amount refunded to the owner if the subscription gets cancelled now
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Net refund amount
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn cancel_refund_estimate(e: Env, subscription_id: u64) -> u64 {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        calc_cancel_refund(&subscription)
+    }
+
+    // Get subscription by ID
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Unique subscription ID
+    //
+    // # Returns
+    //
+    // Subscription data
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn get_subscription(e: Env, subscription_id: u64) -> Subscription {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        e.get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound))
+    }
+
+    // Check whether the given address owns a subscription
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `who` - Address to check
+    //
+    // # Returns
+    //
+    // True if the address matches the subscription owner, false otherwise, if the subscription does not exist or the storage is not migrated
+    pub fn is_owner(e: Env, subscription_id: u64, who: Address) -> bool {
+        // Legacy records can't be decoded until migrated
+        if !is_schema_current(&e) {
+            return false;
+        }
+        e.get_subscription(subscription_id)
+            .map(|subscription| subscription.owner == who)
+            .unwrap_or(false)
+    }
+
+    // Check whether a price move would trigger a subscription
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    // * `price_move_permille` - Price movement relative to the last price, in ‰
+    //
+    // # Returns
+    //
+    // True if the move reaches the subscription relative threshold
+    // False if the subscription does not exist, uses an absolute threshold or the storage is not migrated
+    pub fn would_trigger(e: Env, subscription_id: u64, price_move_permille: u32) -> bool {
+        // Legacy records can't be decoded until migrated
+        if !is_schema_current(&e) {
+            return false;
+        }
+        e.get_subscription(subscription_id)
+            .map(|subscription| match subscription.threshold {
+                ThresholdKind::Relative(threshold) => price_move_permille >= threshold,
+                ThresholdKind::Absolute(_) => false,
+            })
+            .unwrap_or(false)
+    }
+
+    // Get the token address a subscription balance is denominated in
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Token address
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn subscription_token(e: Env, subscription_id: u64) -> Address {
+        panic_if_not_initialized(&e);
+        if !e.has_subscription(subscription_id) {
+            e.panic_with_error(Error::SubscriptionNotFound);
+        }
+        // All subscription balances are currently held in the contract-wide token
+        e.get_token()
+    }
+
+    // Calculate daily retention fee for a given subscription
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Daily retention fees
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn get_retention_fee(e: Env, subscription_id: u64) -> u64 {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        // Calculate daily retention fee based on subscription params
+        calc_subscription_fee(&e, &subscription)
+    }
+
+    // Calculate daily retention fees for several subscriptions
+    //
+    // # Arguments
+    //
+    // * `subscription_ids` - List of subscription IDs
+    //
+    // # Returns
+    //
+    // Daily retention fees in the same order as IDs, 0 for missing subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the batch size exceeds the limit
+    pub fn get_retention_fees(e: Env, subscription_ids: Vec<u64>) -> Vec<u64> {
+        panic_if_not_initialized(&e);
+        panic_if_too_many_items(&e, subscription_ids.len());
+        let mut fees = Vec::new(&e);
+        for subscription_id in subscription_ids.iter() {
+            let fee = match e.get_subscription(subscription_id) {
+                Some(subscription) => calc_subscription_fee(&e, &subscription),
+                None => 0,
+            };
+            fees.push_back(fee);
+        }
+        fees
+    }
+
+    // Get statuses of several subscriptions without loading full records on the client side
+    //
+    // # Arguments
+    //
+    // * `subscription_ids` - List of subscription IDs
+    //
+    // # Returns
+    //
+    // Subscription statuses in the same order as IDs, None for missing subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the batch size exceeds the limit
+    pub fn get_statuses(e: Env, subscription_ids: Vec<u64>) -> Vec<Option<SubscriptionStatus>> {
+        panic_if_not_initialized(&e);
+        panic_if_too_many_items(&e, subscription_ids.len());
+        let mut statuses = Vec::new(&e);
+        for subscription_id in subscription_ids.iter() {
+            statuses.push_back(
+                e.get_subscription(subscription_id)
+                    .map(|subscription| subscription.status),
+            );
+        }
+        statuses
+    }
+
+    // Get computed subscription economics in a single read
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Daily retention fee, runway in days, revival fee, and storage TTL in ledgers based on the balance
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn subscription_economics(e: Env, subscription_id: u64) -> (u64, u32, u64, u32) {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        let retention_fee = calc_subscription_fee(&e, &subscription);
+        let runway_days =
+            calc_runway_days(&subscription, retention_fee).min(u32::MAX as u64) as u32;
+        // Reactivation of a suspended subscription burns the daily retention fee
+        let revival_fee = retention_fee;
+        let ttl_ledgers = calc_subscription_ttl(&e, &subscription);
+        (retention_fee, runway_days, revival_fee, ttl_ledgers)
+    }
+
+    // Get the number of full days a subscription has been suspended
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Number of days since the suspension, 0 for active subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn suspended_days(e: Env, subscription_id: u64) -> u32 {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        match subscription.suspended_at {
+            Some(suspended_at) => now(&e)
+                .saturating_sub(suspended_at)
+                .checked_div(DAY)
+                .unwrap()
+                .min(u32::MAX as u64) as u32,
+            None => 0,
+        }
+    }
+
+    // Project the time when the subscription gets suspended at the current fee
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Timestamp of the charge that suspends the subscription, in milliseconds
+    // 0 for subscriptions that are not active, u64::MAX for free tier subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn projected_suspension_at(e: Env, subscription_id: u64) -> u64 {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        if subscription.status != SubscriptionStatus::Active {
+            return 0;
+        }
+        let fee = calc_subscription_fee(&e, &subscription);
+        // Subscription can only be suspended by a charge, which happens at least one day after the previous one
+        let runway_days = calc_runway_days(&subscription, fee).max(1);
+        runway_days
+            .checked_mul(DAY)
+            .and_then(|period| subscription.last_charged.checked_add(period))
+            .unwrap_or(u64::MAX)
+    }
+
+    // Get the timestamp the subscription is next due for charging
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // Next billing boundary timestamp in milliseconds, 0 for subscriptions that are not active
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn next_charge_at(e: Env, subscription_id: u64) -> u64 {
+        panic_if_not_initialized(&e);
+        // Load subscription
+        let subscription = e
+            .get_subscription(subscription_id)
+            .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+        if subscription.status != SubscriptionStatus::Active {
+            return 0;
+        }
+        subscription.last_charged.checked_add(DAY).unwrap()
+    }
+
+    // Verify internal consistency of a stored subscription record
+    // Intended for monitoring, violations are reported instead of reverting
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // True if the record is consistent, false if any invariant is violated, the subscription does not exist or the storage is not migrated
+    pub fn check_invariants(e: Env, subscription_id: u64) -> bool {
+        // Legacy records can't be decoded until migrated
+        if !is_schema_current(&e) {
+            return false;
+        }
+        let subscription = match e.get_subscription(subscription_id) {
+            Some(subscription) => subscription,
+            None => return false,
+        };
+        // Suspension timestamps must match the status
+        let is_consistent_status = match subscription.status {
+            SubscriptionStatus::Active => {
+                subscription.suspended_at.is_none() && subscription.outage_at.is_none()
+            }
+            SubscriptionStatus::Suspended => subscription.suspended_at.is_some(),
+            SubscriptionStatus::Expired => true,
+            SubscriptionStatus::PendingDeletion => subscription.cancelled_at.is_some(),
+            SubscriptionStatus::SourceOutage => subscription.outage_at.is_some(),
+        };
+        is_consistent_status
+            && subscription.updated <= now(&e)
+            && subscription.last_charged <= now(&e)
+            && is_valid_heartbeat(subscription.heartbeat, subscription.heartbeat_unit)
+            && is_valid_threshold(&subscription.threshold)
+    }
+
+    // Find active subscriptions that will run out of funds soon
+    // Sequentially allocated IDs are scanned first, followed by the index of derived and remapped IDs
+    //
+    // # Arguments
+    //
+    // * `within_days` - Runway threshold, in days
+    // * `cursor` - Cursor returned by the previous page, 0 to start from the beginning
+    // * `limit` - Maximum number of IDs to scan (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // IDs of active subscriptions with the runway below the threshold, and the cursor for the next page (0 if the scan is complete or the limit is 0)
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn subscriptions_at_risk(
+        e: Env,
+        within_days: u32,
+        cursor: u64,
+        limit: u32,
+    ) -> (Vec<u64>, u64) {
+        panic_if_not_initialized(&e);
+        let mut result = Vec::new(&e);
+        let (subscription_ids, next_cursor) = calc_scan_page(&e, cursor, limit);
+        for subscription_id in subscription_ids.iter() {
+            if let Some(subscription) = e.get_subscription(subscription_id) {
+                if subscription.status != SubscriptionStatus::Active {
+                    continue;
+                }
+                let fee = calc_subscription_fee(&e, &subscription);
+                if calc_runway_days(&subscription, fee) < within_days as u64 {
+                    result.push_back(subscription_id);
+                }
+            }
+        }
+        (result, next_cursor)
+    }
+
+    // Sum daily retention fees of active subscriptions over a page of subscription IDs
+    // Sequentially allocated IDs are scanned first, followed by the index of derived and remapped IDs
+    //
+    // # Arguments
+    //
+    // * `start_after_id` - Cursor returned by the previous page, 0 to start from the beginning
+    // * `limit` - Maximum number of IDs to scan (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // Projected daily fees of the scanned page, to be summed across pages by the caller, and the cursor for the next page (0 if the scan is complete or the limit is 0)
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn daily_run_rate(e: Env, start_after_id: u64, limit: u32) -> (u64, u64) {
+        panic_if_not_initialized(&e);
+        let mut run_rate: u64 = 0;
+        let (subscription_ids, next_cursor) = calc_scan_page(&e, start_after_id, limit);
+        for subscription_id in subscription_ids.iter() {
+            if let Some(subscription) = e.get_subscription(subscription_id) {
+                if subscription.status != SubscriptionStatus::Active {
+                    continue;
+                }
+                run_rate = run_rate
+                    .checked_add(calc_subscription_fee(&e, &subscription))
+                    .unwrap();
+            }
+        }
+        (run_rate, next_cursor)
+    }
+
+    // Find subscriptions tracking the given asset pair
+    //
+    // # Arguments
+    //
+    // * `base` - Base symbol
+    // * `quote` - Quote symbol
+    // * `limit` - Maximum number of IDs to return (capped at the batch size limit)
+    // * `start_after_id` - Last ID of the previous page, 0 to start from the beginning of the pair index
+    //
+    // # Returns
+    //
+    // IDs of subscriptions with exactly this base and quote, in the order they were indexed
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn subscriptions_for_pair(
+        e: Env,
+        base: TickerAsset,
+        quote: TickerAsset,
+        limit: u32,
+        start_after_id: u64,
+    ) -> Vec<u64> {
+        panic_if_not_initialized(&e);
+        e.get_pair_index_page(
+            &calc_pair_key(&e, &base, &quote),
+            start_after_id,
+            limit.min(MAX_BATCH_SIZE),
+        )
+    }
+
+    // Get full records of subscriptions owned by the given account
+    // Requires the owner authorization since records expose balances and webhooks
+    //
+    // # Arguments
+    //
+    // * `owner` - Subscription owner address
+    // * `start_after_id` - Last ID of the previous page, 0 to start from the beginning of the owner index
+    // * `limit` - Maximum number of records to return (capped at the batch size limit)
+    //
+    // # Returns
+    //
+    // Subscription IDs with corresponding records, in the order they were indexed
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the caller doesn't match the owner address
+    pub fn get_owner_subscriptions_full(
+        e: Env,
+        owner: Address,
+        start_after_id: u64,
+        limit: u32,
+    ) -> Vec<(u64, Subscription)> {
+        panic_if_not_initialized(&e);
+        owner.require_auth();
+        let page = e.get_owner_index_page(&owner, start_after_id, limit.min(MAX_BATCH_SIZE));
+        let mut result = Vec::new(&e);
+        for subscription_id in page.iter() {
+            // Records that have lapsed are skipped
+            if let Some(subscription) = e.get_subscription(subscription_id) {
+                result.push_back((subscription_id, subscription));
+            }
+        }
+        result
+    }
+
+    // Calculate daily retention fees for a range of heartbeats
+    //
+    // # Arguments
+    //
+    // * `base` - Base symbol
+    // * `quote` - Quote symbol
+    // * `heartbeats` - List of heartbeats, in minutes
+    //
+    // # Returns
+    //
+    // Daily retention fees in the same order as heartbeats
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the number of heartbeats exceeds the batch size limit
+    // Panics if any of the heartbeats is invalid
+    pub fn fee_curve(
+        e: Env,
+        base: TickerAsset,
+        quote: TickerAsset,
+        heartbeats: Vec<u32>,
+    ) -> Vec<u64> {
+        panic_if_not_initialized(&e);
+        panic_if_too_many_items(&e, heartbeats.len());
+        let mut fees = Vec::new(&e);
+        for heartbeat in heartbeats.iter() {
+            if MIN_HEARTBEAT > heartbeat {
+                e.panic_with_error(Error::InvalidHeartbeat);
+            }
+            fees.push_back(calc_contract_fee(
+                &e,
+                &base,
+                &quote,
+                calc_heartbeat_seconds(heartbeat, HeartbeatUnit::Minutes),
+            ));
+        }
+        fees
+    }
+
+    // Get the fee complexity factor applied to a pair
+    //
+    // # Arguments
+    //
+    // * `base` - Base symbol
+    // * `quote` - Quote symbol
+    //
+    // # Returns
+    //
+    // Number of distinct price sources involved, at least 1
+    pub fn complexity_factor(_e: Env, base: TickerAsset, quote: TickerAsset) -> u64 {
+        calc_complexity_factor(&[&base, &quote])
+    }
+
+    // Get the last subscription ID
+    //
+    // # Returns
+    //
+    // Last subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn last_id(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        // Retrieve the last value from the subscription ID counter
+        e.get_last_subscription_id()
+    }
+
+    // Get the ID the next subscription created without a nonce will receive
+    // The value is only a hint, concurrent creations may take it first
+    //
+    // # Returns
+    //
+    // Next sequential subscription ID
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn next_id(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        calc_vacant_subscription_id(&e)
+    }
+
+    // Get recorded balance snapshots of the subscription, oldest first
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // List of (timestamp, balance) snapshots
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn balance_history(e: Env, subscription_id: u64) -> Vec<(u64, u64)> {
+        panic_if_not_initialized(&e);
+        if !e.has_subscription(subscription_id) {
+            e.panic_with_error(Error::SubscriptionNotFound);
+        }
+        e.get_balance_history(subscription_id)
+            .unwrap_or_else(|| Vec::new(&e))
+    }
+
+    // Get the off-chain notification config commitment of the subscription
+    //
+    // # Arguments
+    //
+    // * `subscription_id` - Subscription ID
+    //
+    // # Returns
+    //
+    // SHA-256 hash of the config document, None if no commitment has been made
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    // Panics if the subscription is not found
+    pub fn config_hash(e: Env, subscription_id: u64) -> Option<BytesN<32>> {
+        panic_if_not_initialized(&e);
+        if !e.has_subscription(subscription_id) {
+            e.panic_with_error(Error::SubscriptionNotFound);
+        }
+        e.get_config_hash(subscription_id)
+    }
+
+    // Get the maximum number of balance snapshots kept per subscription
+    //
+    // # Returns
+    //
+    // Maximum number of snapshots, 0 if the history is disabled
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn balance_history_len(e: Env) -> u32 {
+        panic_if_not_initialized(&e);
+        e.get_balance_history_len()
+    }
+
+    // Get absolute minimum daily retention fee
+    //
+    // # Returns
+    //
+    // Minimum fee, 0 if not set
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn min_fee_floor(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_min_fee_floor()
+    }
+
+    // Get minimum amount accepted by a deposit
+    //
+    // # Returns
+    //
+    // Minimum deposit amount, 0 if not set
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn min_deposit(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_min_deposit()
+    }
+
+    // Get the period during which a soft-cancelled subscription can be restored
+    //
+    // # Returns
+    //
+    // Recovery window, in milliseconds
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn recovery_window(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        get_recovery_window(&e)
+    }
+
+    // Get the rounding policy applied to calculated retention fees
+    //
+    // # Returns
+    //
+    // Active rounding mode
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn rounding_mode(e: Env) -> RoundingMode {
+        panic_if_not_initialized(&e);
+        e.get_rounding_mode()
+    }
+
+    // Get the treasury fee recipient and its share
+    //
+    // # Returns
+    //
+    // Treasury address and share of fees in basis points, None if all fees are burned
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn treasury(e: Env) -> Option<(Address, u32)> {
+        panic_if_not_initialized(&e);
+        e.get_treasury()
+            .map(|treasury| (treasury, e.get_treasury_share_bps()))
+    }
+
+    // Get the list of zero-fee asset pair and heartbeat combinations
+    //
+    // # Returns
+    //
+    // List of base, quote and heartbeat (in minutes) combinations
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn free_tier(e: Env) -> Vec<(TickerAsset, TickerAsset, u32)> {
+        panic_if_not_initialized(&e);
+        e.get_free_tier()
+    }
+
+    // Get the heartbeat and threshold applied to new subscriptions that don't specify them
+    //
+    // # Returns
+    //
+    // Default heartbeat in minutes and threshold, or None if not set
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn defaults(e: Env) -> Option<(u32, ThresholdKind)> {
+        panic_if_not_initialized(&e);
+        e.get_defaults()
+    }
+
+    // Get the allowlist of price sources
+    //
+    // # Returns
+    //
+    // List of allowed price sources, empty if the check is disabled
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn sources(e: Env) -> Vec<String> {
+        panic_if_not_initialized(&e);
+        e.get_sources()
+    }
+
+    // Get the table of assets with a custom creation fee multiplier
+    //
+    // # Returns
+    //
+    // List of assets with the creation fee multiplier of the daily retention fee
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn asset_tiers(e: Env) -> Vec<(TickerAsset, u32)> {
+        panic_if_not_initialized(&e);
+        e.get_asset_tiers()
+    }
+
+    // Get the creation fee multiplier applied to a pair
+    //
+    // # Arguments
+    //
+    // * `base` - Base symbol
+    // * `quote` - Quote symbol
+    //
+    // # Returns
+    //
+    // Highest multiplier of the pair assets, or the default multiplier if neither asset has a tier
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn init_multiplier(e: Env, base: TickerAsset, quote: TickerAsset) -> u32 {
+        panic_if_not_initialized(&e);
+        calc_init_multiplier(&e, &base, &quote)
+    }
+
+    // Get the prefix every encrypted webhook must start with
+    //
+    // # Returns
+    //
+    // Expected ciphertext prefix, None if the check is disabled
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn webhook_prefix(e: Env) -> Option<Bytes> {
+        panic_if_not_initialized(&e);
+        e.get_webhook_prefix()
+    }
+
+    // Get the first subscription ID allocated to user subscriptions
+    //
+    // # Returns
+    //
+    // First user subscription ID, IDs below it are reserved for system subscriptions
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn user_id_start(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_user_id_start()
+    }
+
+    // Get the number of active subscriptions
+    //
+    // # Returns
+    //
+    // Number of subscriptions with the active status
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn active_count(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_active_count()
+    }
+
+    // Get the sum of all subscription balances
+    //
+    // # Returns
+    //
+    // Total amount locked in subscription balances
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn total_locked(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_total_locked()
+    }
+
+    // Get the number of live subscriptions
+    //
+    // # Returns
+    //
+    // Number of subscriptions currently stored in the contract
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn subscription_count(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_subscription_count()
+    }
+
+    // Derive deterministic subscription ID that will be assigned to a subscription created with a nonce
+    //
+    // # Arguments
+    //
+    // * `owner` - Subscription owner address
+    // * `base` - Base symbol
+    // * `quote` - Quote symbol
+    // * `nonce` - Arbitrary nonce chosen by the owner
+    //
+    // # Returns
+    //
+    // Subscription ID
+    pub fn derive_subscription_id(
+        e: Env,
+        owner: Address,
+        base: TickerAsset,
+        quote: TickerAsset,
+        nonce: u64,
+    ) -> u64 {
+        calc_subscription_id(&e, &owner, &base, &quote, nonce)
+    }
+
+    // Check whether the contract has been configured
+    //
+    // # Returns
+    //
+    // True if all configuration keys are present
+    pub fn initialized(e: Env) -> bool {
+        e.is_initialized()
+    }
+
+    // Get contract admin address
+    //
+    // # Returns
+    //
+    // Contract admin account address
+    pub fn admin(e: Env) -> Option<Address> {
+        e.get_admin()
+    }
+
+    // Get worker account address
+    //
+    // # Returns
+    //
+    // Worker account address, if set
+    pub fn worker(e: Env) -> Option<Address> {
+        e.get_worker()
+    }
+
+    // Get the whole contract configuration in a single read
+    //
+    // # Returns
+    //
+    // Contract configuration
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn get_full_config(e: Env) -> FullConfig {
+        panic_if_not_initialized(&e);
+        FullConfig {
+            admin: e.get_admin().unwrap(),
+            worker: e.get_worker(),
+            token: e.get_token(),
+            token_decimals: get_token_decimals(&e),
+            init_token: e.get_init_token(),
+            fee: e.get_fee(),
+            min_fee_floor: e.get_min_fee_floor(),
+            min_fee_change_interval: e.get_min_fee_change_interval(),
+            protocol_version: Self::version(e.clone()),
+            trigger_interval: e.get_trigger_interval(),
+            defer_burns: e.get_defer_burns(),
+            pending_burn: e.get_pending_burn(),
+            user_id_start: e.get_user_id_start(),
+            balance_history_len: e.get_balance_history_len(),
+            webhook_prefix: e.get_webhook_prefix(),
+            features: e.get_features(),
+            paused: e.get_paused(),
+            treasury: e.get_treasury(),
+            treasury_share_bps: e.get_treasury_share_bps(),
+            min_deposit: e.get_min_deposit(),
+            recovery_window: get_recovery_window(&e),
+            rounding_mode: e.get_rounding_mode(),
+            deposits_paused: e.get_deposits_paused(),
+            charges_paused: e.get_charges_paused(),
+            sources: e.get_sources(),
+            defaults: match e.get_defaults() {
+                Some(defaults) => Vec::from_array(&e, [defaults]),
+                None => Vec::new(&e),
+            },
+            asset_tiers: e.get_asset_tiers(),
+            free_tier: e.get_free_tier(),
+        }
+    }
+
+    // Check whether a capability is enabled on this deployment
+    //
+    // # Arguments
+    //
+    // * `feature` - Capability flag
+    //
+    // # Returns
+    //
+    // True if the capability is enabled
+    pub fn supports(e: Env, feature: Symbol) -> bool {
+        e.get_features().contains(&feature)
+    }
+
+    // Get the storage schema version
+    //
+    // # Returns
+    //
+    // Storage schema version, 0 for contracts deployed before the schema versioning
+    pub fn schema_version(e: Env) -> u32 {
+        e.get_schema_version().unwrap_or(0)
+    }
+
+    // Get contract version
+    //
+    // # Returns
+    //
+    // Contract protocol version
+    pub fn version(e: Env) -> u32 {
+        // Retrieve stored protocol version, fall back to the cargo package info for contracts configured before it was stored
+        e.get_protocol_version()
+            .unwrap_or_else(|| parse_major_version(env!("CARGO_PKG_VERSION")))
+    }
+
+    // Get the history of base fee changes
+    //
+    // # Returns
+    //
+    // Up to 32 most recent timestamp and base fee pairs, oldest first
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn fee_history(e: Env) -> Vec<(u64, u64)> {
+        panic_if_not_initialized(&e);
+        e.get_fee_history()
+    }
+
+    // Get the total amount of fees burned within a range of days
+    //
+    // # Arguments
+    //
+    // * `from_day` - First day index (timestamp divided by day length), inclusive
+    // * `to_day` - Last day index, inclusive
+    //
+    // # Returns
+    //
+    // Sum of fees burned in the retention token, only the last 90 days are retained
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn fees_burned_between(e: Env, from_day: u64, to_day: u64) -> u64 {
+        panic_if_not_initialized(&e);
+        let mut total: u64 = 0;
+        for (day, amount) in e.get_fees_burned().iter() {
+            if day >= from_day && day <= to_day {
+                total = total.checked_add(amount).unwrap();
+            }
+        }
+        total
+    }
+
+    // Get the base fee in effect at a given time
+    //
+    // # Arguments
+    //
+    // * `timestamp` - Timestamp, in milliseconds
+    //
+    // # Returns
+    //
+    // Base fee in effect at the timestamp
+    // The oldest recorded fee is returned for timestamps preceding the history, the current fee if there is no history
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn fee_at(e: Env, timestamp: u64) -> u64 {
+        panic_if_not_initialized(&e);
+        calc_base_fee_at(&e, timestamp)
+    }
+
+    // Get base contract fee (used to calculate amounts charged from the account balance on the daily basis)
     //
     // # Returns
     //
-    // Contract admin account address
-    pub fn admin(e: Env) -> Option<Address> {
-        e.get_admin()
+    // Base fee
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn fee(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        // Retrieve base Reflector subscription fee
+        e.get_fee()
+    }
+
+    // Get minimum interval between base fee changes
+    //
+    // # Returns
+    //
+    // Minimum interval, in milliseconds
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn min_fee_change_interval(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_min_fee_change_interval()
+    }
+
+    // Get minimum interval between triggers
+    //
+    // # Returns
+    //
+    // Minimum interval, in milliseconds
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn trigger_interval(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_trigger_interval()
+    }
+
+    // Check whether the contract is paused
+    //
+    // # Returns
+    //
+    // True if new subscriptions are blocked
+    pub fn paused(e: Env) -> bool {
+        e.get_paused()
+    }
+
+    // Check whether deposits are paused
+    //
+    // # Returns
+    //
+    // True if deposits and new subscriptions funding are blocked
+    pub fn deposits_paused(e: Env) -> bool {
+        e.get_deposits_paused()
+    }
+
+    // Check whether charges are paused
+    //
+    // # Returns
+    //
+    // True if retention fee charges are blocked
+    pub fn charges_paused(e: Env) -> bool {
+        e.get_charges_paused()
+    }
+
+    // Check whether charged fee burns are deferred
+    //
+    // # Returns
+    //
+    // True if burns are deferred
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn defer_burns(e: Env) -> bool {
+        panic_if_not_initialized(&e);
+        e.get_defer_burns()
+    }
+
+    // Get the amount of charged fees awaiting burn
+    //
+    // # Returns
+    //
+    // Pending burn amount
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn pending_burn(e: Env) -> u64 {
+        panic_if_not_initialized(&e);
+        e.get_pending_burn()
+    }
+
+    // Retrieve Reflector token contract address
+    //
+    // # Returns
+    //
+    // Token address
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn token(e: Env) -> Address {
+        panic_if_not_initialized(&e);
+        // Retrieve Reflector token contract address
+        e.get_token()
+    }
+
+    // Get the number of decimals of the retention token
+    //
+    // # Returns
+    //
+    // Token decimals
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn token_decimals(e: Env) -> u32 {
+        panic_if_not_initialized(&e);
+        get_token_decimals(&e)
+    }
+
+    // Retrieve the token contract address used to pay subscription creation fees
+    //
+    // # Returns
+    //
+    // Init token address, or the retention token address if no separate init token is configured
+    //
+    // # Panics
+    //
+    // Panics if the contract is not initialized
+    pub fn init_token(e: Env) -> Address {
+        panic_if_not_initialized(&e);
+        e.get_init_token().unwrap_or_else(|| e.get_token())
+    }
+}
+
+pub fn calc_fee(
+    base_fee: u64,
+    base_symbol: &TickerAsset,
+    quote_symbol: &TickerAsset,
+    heartbeat: u32,
+) -> u64 {
+    calc_fee_seconds(
+        base_fee,
+        base_symbol,
+        quote_symbol,
+        calc_heartbeat_seconds(heartbeat, HeartbeatUnit::Minutes),
+    )
+}
+
+pub fn calc_fee_seconds(
+    base_fee: u64,
+    base_symbol: &TickerAsset,
+    quote_symbol: &TickerAsset,
+    heartbeat_seconds: u64,
+) -> u64 {
+    calc_fee_rounded(
+        base_fee,
+        base_symbol,
+        quote_symbol,
+        heartbeat_seconds,
+        RoundingMode::Floor,
+    )
+}
+
+// Calculate daily retention fee using the given rounding policy
+// Floor truncates the heartbeat fee before applying the complexity factor (legacy behavior)
+// HalfUp applies the complexity factor under the root, so the final fee is rounded only once
+pub fn calc_fee_rounded(
+    base_fee: u64,
+    base_symbol: &TickerAsset,
+    quote_symbol: &TickerAsset,
+    heartbeat_seconds: u64,
+    rounding_mode: RoundingMode,
+) -> u64 {
+    let complexity_factor = calc_complexity_factor(&[base_symbol, quote_symbol]);
+    match rounding_mode {
+        RoundingMode::Floor => calc_hearbeat_fee(base_fee, heartbeat_seconds)
+            .checked_mul(complexity_factor)
+            .unwrap(),
+        RoundingMode::HalfUp => {
+            let base_fee = base_fee.checked_mul(complexity_factor).unwrap();
+            //7200 seconds (120 minutes) is reference heartbeat
+            let numerator = 7200u128
+                .checked_mul((base_fee as u128).checked_pow(2).unwrap())
+                .unwrap();
+            // Minimum fee is base fee
+            (calc_sqrt_half_up(numerator, heartbeat_seconds as u128) as u64).max(base_fee)
+        }
+    }
+}
+
+// Calculate square root of numerator / denominator rounded half up
+fn calc_sqrt_half_up(numerator: u128, denominator: u128) -> u128 {
+    let root = numerator.checked_div(denominator).unwrap().sqrt();
+    // The exact root is at least root + 0.5 when 4 * numerator >= (2 * root + 1)^2 * denominator
+    let midpoint = root
+        .checked_mul(2)
+        .unwrap()
+        .checked_add(1)
+        .unwrap()
+        .checked_pow(2)
+        .unwrap()
+        .checked_mul(denominator)
+        .unwrap();
+    if numerator.checked_mul(4).unwrap() >= midpoint {
+        return root + 1;
+    }
+    root
+}
+
+// Calculate daily retention fee using the current base fee and minimum fee floor
+fn calc_contract_fee(
+    e: &Env,
+    base_symbol: &TickerAsset,
+    quote_symbol: &TickerAsset,
+    heartbeat_seconds: u64,
+) -> u64 {
+    calc_contract_fee_with_base(e, e.get_fee(), base_symbol, quote_symbol, heartbeat_seconds)
+}
+
+// Calculate daily retention fee using the given base fee and minimum fee floor
+fn calc_contract_fee_with_base(
+    e: &Env,
+    base_fee: u64,
+    base_symbol: &TickerAsset,
+    quote_symbol: &TickerAsset,
+    heartbeat_seconds: u64,
+) -> u64 {
+    calc_fee_rounded(
+        base_fee,
+        base_symbol,
+        quote_symbol,
+        heartbeat_seconds,
+        e.get_rounding_mode(),
+    )
+    .max(e.get_min_fee_floor())
+}
+
+// Calculate daily retention fee for the subscription using the current base fee, free tier subscriptions are not charged
+fn calc_subscription_fee(e: &Env, subscription: &Subscription) -> u64 {
+    if subscription.free_tier {
+        return 0;
+    }
+    calc_contract_fee(
+        e,
+        &subscription.base,
+        &subscription.quote,
+        calc_heartbeat_seconds(subscription.heartbeat, subscription.heartbeat_unit),
+    )
+}
+
+// Calculate daily retention fee for the subscription using the base fee effective at the given timestamp
+fn calc_subscription_fee_at(e: &Env, subscription: &Subscription, timestamp: u64) -> u64 {
+    if subscription.free_tier {
+        return 0;
+    }
+    calc_contract_fee_with_base(
+        e,
+        calc_base_fee_at(e, timestamp),
+        &subscription.base,
+        &subscription.quote,
+        calc_heartbeat_seconds(subscription.heartbeat, subscription.heartbeat_unit),
+    )
+}
+
+// Look up the base fee effective at the given timestamp in the fee history
+fn calc_base_fee_at(e: &Env, timestamp: u64) -> u64 {
+    let history = e.get_fee_history();
+    let mut fee = match history.first() {
+        Some((_, fee)) => fee,
+        None => return e.get_fee(),
+    };
+    for (changed_at, changed_fee) in history.iter() {
+        if changed_at > timestamp {
+            break;
+        }
+        fee = changed_fee;
+    }
+    fee
+}
+
+// Calculate the amount refunded to the owner on cancellation
+// No cancellation fees are applied, so the owner gets the whole remaining balance
+fn calc_cancel_refund(subscription: &Subscription) -> u64 {
+    subscription.balance
+}
+
+// Calculate number of full days the chargeable subscription balance covers, unlimited if the subscription is not charged
+fn calc_runway_days(subscription: &Subscription, fee: u64) -> u64 {
+    subscription
+        .balance
+        .saturating_sub(subscription.reserve)
+        .checked_div(fee)
+        .unwrap_or(u64::MAX)
+}
+
+// Normalize heartbeat to seconds
+fn calc_heartbeat_seconds(heartbeat: u32, heartbeat_unit: HeartbeatUnit) -> u64 {
+    match heartbeat_unit {
+        HeartbeatUnit::Minutes => (heartbeat as u64).checked_mul(60).unwrap(),
+        HeartbeatUnit::Seconds => heartbeat as u64,
+    }
+}
+
+fn calc_hearbeat_fee(base_fee: u64, heartbeat_seconds: u64) -> u64 {
+    //7200 seconds (120 minutes) is reference heartbeat
+    let hearbeat_fee = 7200u128
+        .checked_mul((base_fee as u128).checked_pow(2).unwrap())
+        .unwrap()
+        .checked_div(heartbeat_seconds as u128)
+        .unwrap()
+        .sqrt() as u64;
+    if hearbeat_fee < base_fee {
+        // Minimum fee is base fee
+        return base_fee;
+    }
+    hearbeat_fee
+}
+
+// Calculate complexity factor as 1 + number of extra distinct price sources involved
+fn calc_complexity_factor(assets: &[&TickerAsset]) -> u64 {
+    let mut distinct_sources: u64 = 0;
+    for (i, asset) in assets.iter().enumerate() {
+        // Count only the first occurrence of each source
+        if !assets[..i].iter().any(|prev| prev.source == asset.source) {
+            distinct_sources += 1;
+        }
+    }
+    distinct_sources.max(1)
+}
+
+// Validate subscription params, charge the creation fee and store new subscription
+// The amount is transferred from the owner unless the tokens are already held by the contract
+fn create_subscription(
+    e: &Env,
+    new_subscription: SubscriptionInitParams,
+    amount: u64,
+    idempotency_key: Option<BytesN<32>>,
+    transfer_amount: bool,
+) -> (u64, Subscription) {
+    // Owner authorization is checked by the caller
+    if e.get_paused() {
+        e.panic_with_error(Error::ContractPaused);
+    }
+    // Subscriptions funded from the existing balances bring no new tokens
+    if transfer_amount && e.get_deposits_paused() {
+        e.panic_with_error(Error::DepositsPaused);
+    }
+    // Return the previously created subscription if the request is a retry
+    if let Some(idempotency_key) = &idempotency_key {
+        if let Some(subscription_id) =
+            e.get_idempotency_key(&new_subscription.owner, idempotency_key)
+        {
+            if let Some(subscription) = e.get_subscription(subscription_id) {
+                return (subscription_id, subscription);
+            }
+        }
+    }
+    // Calculate daily retention fee based on subscription params
+    let heartbeat_seconds = calc_heartbeat_seconds(
+        resolve_heartbeat(e, &new_subscription),
+        new_subscription.heartbeat_unit,
+    );
+    let retention_fee = calc_contract_fee(
+        e,
+        &new_subscription.base,
+        &new_subscription.quote,
+        heartbeat_seconds,
+    );
+    // Allowlisted combinations are not charged at all
+    let free_tier = is_free_tier(
+        e,
+        &new_subscription.base,
+        &new_subscription.quote,
+        heartbeat_seconds,
+    );
+    // Creation fee is a multiple of the daily retention fee depending on the asset tier
+    let init_fee = if free_tier {
+        0
+    } else {
+        calc_init_fee(
+            e,
+            &new_subscription.base,
+            &new_subscription.quote,
+            retention_fee,
+        )
+    };
+    // Creation fee is taken from the deposited amount unless it is paid in a separate init token
+    let init_token = e.get_init_token();
+    let init_cost = if init_token.is_some() { 0 } else { init_fee };
+    // Check the amount, subscriptions paying the creation fee in the init token must be funded for at least one day
+    let min_amount = if init_token.is_some() && !free_tier {
+        retention_fee
+    } else {
+        init_cost
+    };
+    if amount < min_amount {
+        e.panic_with_error(Error::InvalidAmount);
+    }
+    panic_if_invalid_params(e, &new_subscription);
+    // Check that the reserve is covered by the initial balance
+    if new_subscription.reserve > amount.checked_sub(init_cost).unwrap() {
+        e.panic_with_error(Error::InvalidReserve);
+    }
+    // Resolve subscription ID - derive it from the nonce if provided, otherwise use sequential counter
+    let subscription_id = match new_subscription.id_nonce {
+        Some(nonce) => {
+            let subscription_id = calc_subscription_id(
+                e,
+                &new_subscription.owner,
+                &new_subscription.base,
+                &new_subscription.quote,
+                nonce,
+            );
+            // Derived IDs must not take existing or reserved system IDs
+            if e.has_subscription(subscription_id) || subscription_id < e.get_user_id_start() {
+                e.panic_with_error(Error::IdCollision);
+            }
+            subscription_id
+        }
+        None => calc_vacant_subscription_id(e),
+    };
+    // Limit the number of free subscriptions per owner to prevent abuse
+    if free_tier {
+        increment_free_tier_count(e, &new_subscription.owner);
+    }
+    // Transfer and burn the tokens
+    let token = e.get_token();
+    match init_token {
+        Some(init_token) if init_fee > 0 => {
+            deposit(e, &init_token, &new_subscription.owner, init_fee);
+            burn(e, &init_token, init_fee, init_fee);
+            if transfer_amount {
+                deposit(e, &token, &new_subscription.owner, amount);
+            }
+        }
+        _ => {
+            if transfer_amount {
+                deposit(e, &token, &new_subscription.owner, amount);
+            }
+            burn(e, &token, init_fee, amount);
+            // Burn statistics only track the retention token
+            record_fees_burned(e, init_fee - calc_treasury_share(e, init_fee));
+        }
+    }
+    if new_subscription.id_nonce.is_none() {
+        e.set_last_subscription_id(subscription_id);
+    } else {
+        e.add_derived_index_entry(subscription_id);
+    }
+    if let Some(idempotency_key) = &idempotency_key {
+        e.set_idempotency_key(
+            &new_subscription.owner,
+            idempotency_key,
+            subscription_id,
+            IDEMPOTENCY_KEY_TTL,
+        );
+    }
+    // Create subscription itself
+    let mut subscription =
+        init_subscription(e, new_subscription, amount.checked_sub(init_cost).unwrap());
+    subscription.free_tier = free_tier;
+    // Extend TTL based on the subscription retention fee and balance, free subscriptions are kept alive as long as possible
+    let ledgers_to_live = if free_tier {
+        e.storage().max_ttl()
+    } else {
+        calc_ledgers_to_live(e, retention_fee, subscription.balance)
+    };
+    store_new_subscription(e, subscription_id, subscription, ledgers_to_live)
+}
+
+// Append base fee change to the fee history, evicting the oldest entries past the cap
+fn record_fee_change(e: &Env, fee: u64) {
+    let mut history = e.get_fee_history();
+    while history.len() >= MAX_FEE_HISTORY {
+        history.pop_front();
+    }
+    history.push_back((now(e), fee));
+    e.set_fee_history(&history);
+}
+
+// Calculate the next sequential subscription ID, skipping the reserved system ID range
+fn calc_next_subscription_id(e: &Env) -> u64 {
+    e.get_last_subscription_id()
+        .max(e.get_user_id_start().checked_sub(1).unwrap())
+        .checked_add(1)
+        .unwrap()
+}
+
+// Get the upper bound for scans over sequentially allocated subscription IDs
+fn calc_last_sequential_id(e: &Env) -> u64 {
+    calc_next_subscription_id(e) - 1
+}
+
+// Calculate the next sequential subscription ID, skipping IDs taken by remapped or derived records
+fn calc_vacant_subscription_id(e: &Env) -> u64 {
+    let mut subscription_id = calc_next_subscription_id(e);
+    while e.has_subscription(subscription_id) {
+        subscription_id = subscription_id.checked_add(1).unwrap();
+    }
+    subscription_id
+}
+
+// Add collected fees to the bucket of the current day, evicting buckets past the retention window
+fn record_fees_burned(e: &Env, amount: u64) {
+    if amount == 0 {
+        return;
+    }
+    let day = now(e) / DAY;
+    let mut buckets = e.get_fees_burned();
+    while let Some((bucket_day, _)) = buckets.first() {
+        if bucket_day + MAX_FEE_BUCKETS > day {
+            break;
+        }
+        buckets.pop_front();
+    }
+    match buckets.last() {
+        Some((bucket_day, total)) if bucket_day == day => {
+            buckets.set(buckets.len() - 1, (day, total.checked_add(amount).unwrap()));
+        }
+        _ => buckets.push_back((day, amount)),
+    }
+    e.set_fees_burned(&buckets);
+}
+
+// Append balance snapshot to the subscription history, evicting the oldest snapshots past the cap
+fn record_balance(e: &Env, subscription_id: u64, balance: u64) {
+    let len = e.get_balance_history_len();
+    if len == 0 {
+        return;
+    }
+    let mut history = e
+        .get_balance_history(subscription_id)
+        .unwrap_or_else(|| Vec::new(e));
+    while history.len() >= len {
+        history.pop_front();
+    }
+    history.push_back((now(e), balance));
+    e.set_balance_history(subscription_id, &history);
+}
+
+// Check heartbeat, threshold, webhook and format of the new subscription
+fn panic_if_invalid_params(e: &Env, new_subscription: &SubscriptionInitParams) {
+    // Check subscription heartbeat
+    if !is_valid_heartbeat(
+        resolve_heartbeat(e, new_subscription),
+        new_subscription.heartbeat_unit,
+    ) {
+        e.panic_with_error(Error::InvalidHeartbeat);
+    }
+    // Check threshold
+    if !is_valid_threshold(&resolve_threshold(e, new_subscription)) {
+        e.panic_with_error(Error::InvalidThreshold);
+    }
+    // Check subscription webhook size
+    if new_subscription.webhook.len() > MAX_WEBHOOK_SIZE {
+        e.panic_with_error(Error::WebhookTooLong);
+    }
+    // Check that the webhook looks encrypted if the prefix check is enabled
+    if let Some(prefix) = e.get_webhook_prefix() {
+        let webhook = &new_subscription.webhook;
+        if webhook.len() < prefix.len() || webhook.slice(..prefix.len()) != prefix {
+            e.panic_with_error(Error::WebhookNotEncrypted);
+        }
+    }
+    // Check that both assets reference known price sources if the allowlist is enabled
+    let sources = e.get_sources();
+    if !sources.is_empty()
+        && (!sources.contains(&new_subscription.base.source)
+            || !sources.contains(&new_subscription.quote.source))
+    {
+        e.panic_with_error(Error::UnknownSource);
+    }
+    // Check notification format
+    panic_if_invalid_format(e, new_subscription.format);
+    // Check that the expiry date is in the future
+    if let Some(expires_at) = new_subscription.expires_at {
+        if expires_at <= now(e) {
+            e.panic_with_error(Error::InvalidTimestamp);
+        }
+    }
+    // Check the notification window, both bounds must be set within a day
+    let is_valid_window = match (
+        new_subscription.active_from_minute,
+        new_subscription.active_to_minute,
+    ) {
+        (None, None) => true,
+        (Some(from), Some(to)) => from < MINUTES_PER_DAY && to < MINUTES_PER_DAY,
+        _ => false,
+    };
+    if !is_valid_window {
+        e.panic_with_error(Error::InvalidTimeWindow);
+    }
+}
+
+// Get the subscription heartbeat, falling back to the contract default converted to the subscription units
+fn resolve_heartbeat(e: &Env, new_subscription: &SubscriptionInitParams) -> u32 {
+    if let Some(heartbeat) = new_subscription.heartbeat {
+        return heartbeat;
+    }
+    let (heartbeat, _) = e
+        .get_defaults()
+        .unwrap_or_else(|| panic_with_error!(e, Error::InvalidHeartbeat));
+    match new_subscription.heartbeat_unit {
+        HeartbeatUnit::Minutes => heartbeat,
+        HeartbeatUnit::Seconds => heartbeat.checked_mul(60).unwrap(),
+    }
+}
+
+// Get the subscription threshold, falling back to the contract default
+fn resolve_threshold(e: &Env, new_subscription: &SubscriptionInitParams) -> ThresholdKind {
+    if let ThresholdParam::Custom(threshold) = &new_subscription.threshold {
+        return threshold.clone();
+    }
+    let (_, threshold) = e
+        .get_defaults()
+        .unwrap_or_else(|| panic_with_error!(e, Error::InvalidThreshold));
+    threshold
+}
+
+// Check that the heartbeat is not shorter than the minimum allowed for its unit
+fn is_valid_heartbeat(heartbeat: u32, heartbeat_unit: HeartbeatUnit) -> bool {
+    let min_heartbeat = match heartbeat_unit {
+        HeartbeatUnit::Minutes => MIN_HEARTBEAT,
+        HeartbeatUnit::Seconds => MIN_HEARTBEAT_SECONDS,
+    };
+    heartbeat >= min_heartbeat
+}
+
+// Check that the threshold is within the allowed range
+fn is_valid_threshold(threshold: &ThresholdKind) -> bool {
+    match *threshold {
+        ThresholdKind::Relative(threshold) => threshold > 0 && threshold <= 10000,
+        ThresholdKind::Absolute(threshold) => threshold > 0,
+    }
+}
+
+// Build a new active subscription from the init params
+fn init_subscription(
+    e: &Env,
+    new_subscription: SubscriptionInitParams,
+    balance: u64,
+) -> Subscription {
+    let threshold = resolve_threshold(e, &new_subscription);
+    let heartbeat = resolve_heartbeat(e, &new_subscription);
+    Subscription {
+        owner: new_subscription.owner,
+        base: new_subscription.base,
+        quote: new_subscription.quote,
+        threshold,
+        heartbeat,
+        heartbeat_unit: new_subscription.heartbeat_unit,
+        webhook: new_subscription.webhook,
+        format: new_subscription.format,
+        balance,
+        reserve: new_subscription.reserve,
+        emit_charge_events: true,
+        auto_cancel_on_suspend: false,
+        trigger_count: 0,
+        last_triggered: None,
+        status: SubscriptionStatus::Active,
+        updated: now(e), // normalize to milliseconds
+        last_charged: now(e),
+        suspended_at: None,
+        outage_at: None,
+        cancelled_at: None,
+        expires_at: new_subscription.expires_at,
+        pending_owner: None,
+        max_acceptable_fee: None,
+        active_from_minute: new_subscription.active_from_minute,
+        active_to_minute: new_subscription.active_to_minute,
+        free_tier: false,
+    }
+}
+
+// Convert a subscription record stored in the legacy layout to the current one
+fn upgrade_legacy_subscription(legacy: LegacySubscription) -> Subscription {
+    let suspended_at = if legacy.status == SubscriptionStatus::Suspended {
+        Some(legacy.updated)
+    } else {
+        None
+    };
+    Subscription {
+        owner: legacy.owner,
+        base: legacy.base,
+        quote: legacy.quote,
+        threshold: ThresholdKind::Relative(legacy.threshold),
+        heartbeat: legacy.heartbeat,
+        heartbeat_unit: HeartbeatUnit::Minutes,
+        webhook: legacy.webhook,
+        format: 0,
+        balance: legacy.balance,
+        reserve: 0,
+        emit_charge_events: true,
+        auto_cancel_on_suspend: false,
+        trigger_count: 0,
+        last_triggered: None,
+        status: legacy.status,
+        updated: legacy.updated,
+        // Legacy records were charged up to the last update
+        last_charged: legacy.updated,
+        suspended_at,
+        outage_at: None,
+        cancelled_at: None,
+        expires_at: None,
+        pending_owner: None,
+        max_acceptable_fee: None,
+        active_from_minute: None,
+        active_to_minute: None,
+        free_tier: false,
     }
+}
 
-    // Get contract version
-    //
-    // # Returns
-    //
-    // Contract protocol version
-    pub fn version(_e: Env) -> u32 {
-        // Retrieve protocol version based on the cargo package info
-        env!("CARGO_PKG_VERSION")
-            .split(".")
-            .next()
+// Add a migrated subscription to the aggregates and indexes
+fn register_migrated_subscription(e: &Env, subscription_id: u64, subscription: &Subscription) {
+    e.set_subscription_count(e.get_subscription_count().checked_add(1).unwrap());
+    let active_change = if subscription.status == SubscriptionStatus::Active {
+        1
+    } else {
+        0
+    };
+    update_totals(e, active_change, subscription.balance as i128);
+    add_to_pair_index(e, subscription_id, subscription);
+    add_to_source_index(e, subscription_id, subscription);
+    add_to_owner_index(e, subscription_id, &subscription.owner);
+}
+
+// Store the new subscription, extend its TTL and publish the created event
+fn store_new_subscription(
+    e: &Env,
+    subscription_id: u64,
+    subscription: Subscription,
+    ledgers_to_live: u32,
+) -> (u64, Subscription) {
+    e.set_subscription(subscription_id, &subscription);
+    e.set_subscription_count(e.get_subscription_count().checked_add(1).unwrap());
+    update_totals(e, 1, subscription.balance as i128);
+    add_to_pair_index(e, subscription_id, &subscription);
+    add_to_source_index(e, subscription_id, &subscription);
+    add_to_owner_index(e, subscription_id, &subscription.owner);
+    extend_subscription_storage_ttl(e, subscription_id, &subscription, ledgers_to_live);
+    // Publish subscription created event
+    let data = (subscription_id, subscription.clone());
+    e.events().publish(
+        (
+            REFLECTOR,
+            symbol_short!("triggers"),
+            symbol_short!("created"),
+            subscription.owner,
+        ),
+        data.clone(),
+    );
+    data
+}
+
+// Load a subscription that can accept deposits
+fn load_depositable_subscription(e: &Env, subscription_id: u64) -> Subscription {
+    let subscription = e
+        .get_subscription(subscription_id)
+        .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+    // Expired subscriptions can't be revived, a new subscription should be created instead
+    if subscription.status == SubscriptionStatus::Expired {
+        e.panic_with_error(Error::SubscriptionExpired);
+    }
+    // Subscriptions pending deletion have to be restored first
+    if subscription.status == SubscriptionStatus::PendingDeletion {
+        e.panic_with_error(Error::InvalidSubscriptionStatusError);
+    }
+    subscription
+}
+
+// Transfer tokens to the subscription balance, reviving it if suspended
+fn top_up_subscription(
+    e: &Env,
+    from: &Address,
+    subscription_id: u64,
+    mut subscription: Subscription,
+    amount: u64,
+) {
+    if e.get_deposits_paused() {
+        e.panic_with_error(Error::DepositsPaused);
+    }
+    // Calculate daily retention fee based on subscription params
+    let retention_fee = calc_subscription_fee(e, &subscription);
+    // Transfer tokens
+    deposit(e, &e.get_token(), from, amount);
+    // Update subscription balance
+    subscription.balance = subscription.balance.checked_add(amount).unwrap();
+    update_totals(e, 0, amount as i128);
+    // Update subscription status if it was suspended
+    if subscription.status == SubscriptionStatus::Suspended {
+        // Revived subscription has to cover the revival fee and at least one more day
+        let chargeable = subscription.balance.saturating_sub(subscription.reserve);
+        if chargeable < retention_fee.checked_mul(2).unwrap() {
+            e.panic_with_error(Error::InsufficientRevivalDeposit);
+        }
+        // Burn tokens as a revival fee
+        burn(e, &e.get_token(), retention_fee, subscription.balance);
+        record_fees_burned(e, retention_fee - calc_treasury_share(e, retention_fee));
+        subscription.balance = subscription.balance.checked_sub(retention_fee).unwrap();
+        update_totals(e, 1, -(retention_fee as i128));
+        // Re-activate saubscription
+        subscription.status = SubscriptionStatus::Active;
+        subscription.suspended_at = None;
+        // Revival fee covers the current day, restart the billing clock
+        subscription.last_charged = now(e);
+    }
+    subscription.updated = now(e);
+    // Update state
+    e.set_subscription(subscription_id, &subscription);
+    record_balance(e, subscription_id, subscription.balance);
+    // Extend TTL based on the subscription retention fee and balance
+    extend_subscription_storage_ttl(
+        e,
+        subscription_id,
+        &subscription,
+        calc_ledgers_to_live(e, retention_fee, subscription.balance),
+    );
+    // Publish subscription deposited event
+    e.events().publish(
+        (
+            REFLECTOR,
+            symbol_short!("triggers"),
+            symbol_short!("deposited"),
+            subscription.owner.clone(),
+        ),
+        (subscription_id, subscription, amount, get_token_decimals(e)),
+    );
+}
+
+// Set or clear the proposed subscription owner
+fn set_pending_owner(e: &Env, subscription_id: u64, pending_owner: Option<Address>) {
+    panic_if_not_initialized(e);
+    // Load subscription
+    let mut subscription = e
+        .get_subscription(subscription_id)
+        .unwrap_or_else(|| panic_with_error!(e, Error::SubscriptionNotFound));
+    // Only owner can propose or cancel the transfer
+    subscription.owner.require_auth();
+    subscription.pending_owner = pending_owner;
+    subscription.updated = now(e);
+    // Update state
+    e.set_subscription(subscription_id, &subscription);
+}
+
+// Refund the subscription balance to the owner and remove the subscription
+fn cancel_subscription(
+    e: &Env,
+    subscription_id: u64,
+    subscription: Subscription,
+    burn_residual: bool,
+    auto_cancelled: bool,
+) {
+    let residual = calc_cancel_refund(&subscription);
+    let burned = if burn_residual {
+        // Donate the whole remaining balance to the burn, bypassing the treasury share
+        burn_tokens(e, &e.get_token(), residual);
+        residual
+    } else {
+        // Transfer the remaining balance to the owner account
+        withdraw(e, &e.get_token(), &subscription.owner, residual);
+        0
+    };
+    remove_subscription_record(e, subscription_id, &subscription);
+    // Publish subscription cancelled event
+    e.events().publish(
+        (
+            REFLECTOR,
+            symbol_short!("triggers"),
+            symbol_short!("cancelled"),
+            subscription.owner,
+        ),
+        (
+            subscription_id,
+            burned,
+            auto_cancelled,
+            get_token_decimals(e),
+        ),
+    );
+}
+
+// Remove subscription from the state, its balance is expected to be settled by the caller
+fn remove_subscription_record(e: &Env, subscription_id: u64, subscription: &Subscription) {
+    e.remove_subscription(subscription_id);
+    e.set_subscription_count(e.get_subscription_count().saturating_sub(1));
+    let active_change = if subscription.status == SubscriptionStatus::Active {
+        -1
+    } else {
+        0
+    };
+    update_totals(e, active_change, -(subscription.balance as i128));
+    remove_from_pair_index(e, subscription_id, subscription);
+    remove_from_source_index(e, subscription_id, subscription);
+    remove_from_owner_index(e, subscription_id, &subscription.owner);
+    e.remove_derived_index_entry(subscription_id);
+    if subscription.free_tier {
+        decrement_free_tier_count(e, &subscription.owner);
+    }
+}
+
+// Charge retention fees accrued by the given timestamp and burn charged tokens
+fn charge_subscriptions(e: &Env, subscription_ids: Vec<u64>, timestamp: u64) -> ChargeResult {
+    if e.get_charges_paused() {
+        e.panic_with_error(Error::ChargesPaused);
+    }
+    // Reject oversized batches before any state mutation
+    panic_if_too_many_items(e, subscription_ids.len());
+    let mut result = ChargeResult {
+        charges: Vec::new(e),
+        suspended: Vec::new(e),
+    };
+    let mut total_charge: u64 = 0;
+    // Aggregate changes applied once for the whole batch
+    let mut active_change: i128 = 0;
+    let mut locked_change: i128 = 0;
+    let user_id_start = e.get_user_id_start();
+    for subscription_id in subscription_ids.iter() {
+        // System subscriptions are not charged
+        if subscription_id < user_id_start {
+            continue;
+        }
+        if let Some(mut subscription) = e.get_subscription(subscription_id) {
+            // Only active subscriptions are charged, suspended ones keep their residual balance untouched
+            if subscription.status != SubscriptionStatus::Active {
+                expire_if_overdue(e, subscription_id, &mut subscription, timestamp);
+                continue;
+            }
+            // Owner-defined expiry stops the subscription regardless of the balance
+            let is_expiring = subscription
+                .expires_at
+                .is_some_and(|expires_at| timestamp >= expires_at);
+            // We can charge fees for several days in case if there was an interruption in background worker charge process
+            let days_charged = calc_charge_until(&subscription, timestamp)
+                .checked_sub(subscription.last_charged)
+                .unwrap_or_else(|| panic_with_error!(e, Error::InvalidTimestamp))
+                .checked_div(DAY)
+                .unwrap();
+            let fee = calc_subscription_fee(e, &subscription);
+            // Free tier subscriptions are never charged
+            if (days_charged == 0 || fee == 0) && !is_expiring {
+                continue;
+            }
+            // Suspend instead of charging if the fee exceeds the owner-defined cap
+            if let Some(max_acceptable_fee) = subscription
+                .max_acceptable_fee
+                .filter(|max_acceptable_fee| !is_expiring && fee > *max_acceptable_fee)
+            {
+                // Settle the elapsed days at the previous fee, never above the owner-defined cap
+                let previous_fee =
+                    calc_subscription_fee_at(e, &subscription, subscription.last_charged)
+                        .min(max_acceptable_fee);
+                let charge = deduct_accrued_fees(&mut subscription, days_charged, previous_fee);
+                locked_change -= charge as i128;
+                subscription.last_charged = timestamp;
+                result.charges.push_back((subscription_id, charge));
+                total_charge = total_charge.checked_add(charge).unwrap();
+                if days_charged > 0 {
+                    publish_charged_event(e, subscription_id, &subscription, charge, timestamp);
+                }
+                record_balance(e, subscription_id, subscription.balance);
+                // Refund the owner instead of suspending if requested
+                if subscription.auto_cancel_on_suspend {
+                    cancel_subscription(e, subscription_id, subscription, false, true);
+                    continue;
+                }
+                subscription.status = SubscriptionStatus::Suspended;
+                subscription.suspended_at = Some(timestamp);
+                subscription.updated = now(e);
+                e.set_subscription(subscription_id, &subscription);
+                active_change -= 1;
+                result.suspended.push_back(subscription_id);
+                // Publish fee exceeded event
+                e.events().publish(
+                    (
+                        REFLECTOR,
+                        symbol_short!("triggers"),
+                        Symbol::new(e, "fee_exceeded"),
+                        subscription.owner.clone(),
+                    ),
+                    (subscription_id, fee, timestamp),
+                );
+                continue;
+            }
+            // Deduct calculated retention fees
+            let charge = deduct_accrued_fees(&mut subscription, days_charged, fee);
+            locked_change -= charge as i128;
+            subscription.last_charged = timestamp;
+            subscription.updated = now(e);
+            result.charges.push_back((subscription_id, charge));
+            // Sum all retention fee charges
+            total_charge = total_charge.checked_add(charge).unwrap();
+            if days_charged > 0 {
+                publish_charged_event(e, subscription_id, &subscription, charge, timestamp);
+            }
+            if is_expiring {
+                // Stop the subscription, refund the remaining balance including the reserve and drop the record
+                withdraw(e, &e.get_token(), &subscription.owner, subscription.balance);
+                remove_subscription_record(e, subscription_id, &subscription);
+                // Publish expired event
+                e.events().publish(
+                    (
+                        REFLECTOR,
+                        symbol_short!("triggers"),
+                        symbol_short!("expired"),
+                        subscription.owner.clone(),
+                    ),
+                    (subscription_id, timestamp),
+                );
+                continue;
+            } else if subscription.balance.saturating_sub(subscription.reserve) < fee {
+                // Refund the remaining balance instead of suspending if requested
+                if subscription.auto_cancel_on_suspend {
+                    cancel_subscription(e, subscription_id, subscription, false, true);
+                    continue;
+                }
+                // Deactivate the subscription if the chargeable balance is less than the daily retention fee
+                subscription.status = SubscriptionStatus::Suspended;
+                subscription.suspended_at = Some(timestamp);
+                active_change -= 1;
+                result.suspended.push_back(subscription_id);
+                // Publish suspended event
+                e.events().publish(
+                    (
+                        REFLECTOR,
+                        symbol_short!("triggers"),
+                        symbol_short!("suspended"),
+                        subscription.owner.clone(),
+                    ),
+                    (subscription_id, timestamp),
+                );
+            }
+            // Update subscription properties
+            e.set_subscription(subscription_id, &subscription);
+            record_balance(e, subscription_id, subscription.balance);
+        } else {
+            // Record subscriptions whose entries expired since the last charge
+            reap_if_lapsed(e, subscription_id);
+        }
+    }
+    // Burn tokens charged from all subscriptions
+    burn_charged_fees(e, total_charge);
+    update_totals(e, active_change, locked_change);
+    // Publish batch statistics event
+    e.events().publish(
+        (REFLECTOR, symbol_short!("triggers"), symbol_short!("stats")),
+        (
+            subscription_ids.len(),
+            total_charge,
+            result.suspended.len(),
+            e.get_active_count(),
+            e.get_total_locked(),
+        ),
+    );
+    result
+}
+
+// Publish charged event unless the owner opted out
+fn publish_charged_event(
+    e: &Env,
+    subscription_id: u64,
+    subscription: &Subscription,
+    charge: u64,
+    timestamp: u64,
+) {
+    if !subscription.emit_charge_events {
+        return;
+    }
+    e.events().publish(
+        (
+            REFLECTOR,
+            symbol_short!("triggers"),
+            symbol_short!("charged"),
+            subscription.owner.clone(),
+        ),
+        (subscription_id, charge, timestamp, get_token_decimals(e)),
+    );
+}
+
+// Calculate the timestamp fees are accrued by, fees are not accrued past the expiry date
+fn calc_charge_until(subscription: &Subscription, timestamp: u64) -> u64 {
+    match subscription.expires_at {
+        Some(expires_at) => timestamp.min(expires_at.max(subscription.last_charged)),
+        None => timestamp,
+    }
+}
+
+// Deduct retention fees for the given number of days from the subscription balance
+// Never charges more than left on the subscription balance above the reserve
+fn deduct_accrued_fees(subscription: &mut Subscription, days_charged: u64, fee: u64) -> u64 {
+    let chargeable = subscription.balance.saturating_sub(subscription.reserve);
+    let charge = days_charged.checked_mul(fee).unwrap().min(chargeable);
+    subscription.balance = subscription.balance.checked_sub(charge).unwrap();
+    charge
+}
+
+// Burn charged retention fees, or accumulate them if burns are deferred
+fn burn_charged_fees(e: &Env, amount: u64) {
+    if amount == 0 {
+        return;
+    }
+    if e.get_defer_burns() {
+        // Deferred fees are recorded as burned once flushed
+        let pending_burn = e.get_pending_burn().checked_add(amount).unwrap();
+        e.set_pending_burn(pending_burn);
+    } else {
+        burn(e, &e.get_token(), amount, amount);
+        record_fees_burned(e, amount - calc_treasury_share(e, amount));
+    }
+}
+
+// Apply changes to the aggregate active subscription count and total locked balance
+fn update_totals(e: &Env, active_change: i128, locked_change: i128) {
+    if active_change != 0 {
+        let active_count = (e.get_active_count() as i128)
+            .checked_add(active_change)
             .unwrap()
-            .parse::<u32>()
+            .max(0);
+        e.set_active_count(active_count as u64);
+    }
+    if locked_change != 0 {
+        let total_locked = (e.get_total_locked() as i128)
+            .checked_add(locked_change)
             .unwrap()
+            .max(0);
+        e.set_total_locked(total_locked as u64);
     }
+}
 
-    // Get base contract fee (used to calculate amounts charged from the account balance on the daily basis)
-    //
-    // # Returns
-    //
-    // Base fee
-    //
-    // # Panics
-    //
-    // Panics if the contract is not initialized
-    pub fn fee(e: Env) -> u64 {
-        panic_if_not_initialized(&e);
-        // Retrieve base Reflector subscription fee
-        e.get_fee()
+// Expire the subscription if it stayed suspended for longer than the expiration period
+fn expire_if_overdue(
+    e: &Env,
+    subscription_id: u64,
+    subscription: &mut Subscription,
+    timestamp: u64,
+) {
+    if subscription.status != SubscriptionStatus::Suspended
+        || timestamp.saturating_sub(subscription.suspended_at.unwrap_or(timestamp))
+            < EXPIRATION_PERIOD
+    {
+        return;
     }
+    subscription.status = SubscriptionStatus::Expired;
+    subscription.updated = now(e);
+    e.set_subscription(subscription_id, subscription);
+    // Publish expired event
+    e.events().publish(
+        (
+            REFLECTOR,
+            symbol_short!("triggers"),
+            symbol_short!("expired"),
+            subscription.owner.clone(),
+        ),
+        (subscription_id, timestamp),
+    );
+}
 
-    // Retrieve Reflector token contract address
-    //
-    // # Returns
-    //
-    // Token address
-    //
-    // # Panics
-    //
-    // Panics if the contract is not initialized
-    pub fn token(e: Env) -> Address {
-        panic_if_not_initialized(&e);
-        // Retrieve Reflector token contract address
-        e.get_token()
+// Reconcile a subscription whose record expired without being removed explicitly
+// Returns true if the subscription has been recorded as lapsed
+fn reap_if_lapsed(e: &Env, subscription_id: u64) -> bool {
+    if e.has_subscription(subscription_id) {
+        return false;
+    }
+    // Explicitly removed subscriptions don't leave a summary behind
+    let summary = match e.get_subscription_summary(subscription_id) {
+        Some(summary) => summary,
+        None => return false,
+    };
+    e.remove_subscription(subscription_id);
+    e.set_subscription_count(e.get_subscription_count().saturating_sub(1));
+    let active_change = if summary.status == SubscriptionStatus::Active {
+        -1
+    } else {
+        0
+    };
+    update_totals(e, active_change, -(summary.balance as i128));
+    let pair_key = calc_pair_key(e, &summary.base, &summary.quote);
+    e.remove_pair_index_entry(&pair_key, subscription_id);
+    for source in calc_asset_sources(e, &summary.base, &summary.quote).iter() {
+        e.remove_source_index_entry(&source, subscription_id);
+    }
+    remove_from_owner_index(e, subscription_id, &summary.owner);
+    e.remove_derived_index_entry(subscription_id);
+    if summary.free_tier {
+        decrement_free_tier_count(e, &summary.owner);
     }
+    // Publish lapsed event
+    e.events().publish(
+        (
+            REFLECTOR,
+            symbol_short!("triggers"),
+            symbol_short!("lapsed"),
+        ),
+        subscription_id,
+    );
+    true
 }
 
-pub fn calc_fee(
-    base_fee: u64,
-    base_symbol: &TickerAsset,
-    quote_symbol: &TickerAsset,
-    heartbeat: u32,
+// Derive subscription ID from the first 8 bytes of hash(owner, base, quote, nonce)
+fn calc_subscription_id(
+    e: &Env,
+    owner: &Address,
+    base: &TickerAsset,
+    quote: &TickerAsset,
+    nonce: u64,
 ) -> u64 {
-    let heartbeat_fee = calc_hearbeat_fee(base_fee, heartbeat);
-    let complexity_factor = calc_complexity_factor(base_symbol, quote_symbol);
-    heartbeat_fee.checked_mul(complexity_factor).unwrap()
+    let data = (owner.clone(), base.clone(), quote.clone(), nonce).to_xdr(e);
+    let hash = e.crypto().sha256(&data).to_array();
+    let mut id_bytes = [0u8; 8];
+    id_bytes.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(id_bytes)
 }
 
-fn calc_hearbeat_fee(base_fee: u64, heartbeat: u32) -> u64 {
-    //120 is reference heartbeat
-    let hearbeat_fee = 120u128
-        .checked_mul((base_fee as u128).checked_pow(2).unwrap())
-        .unwrap()
-        .checked_div(heartbeat as u128)
-        .unwrap()
-        .sqrt() as u64;
-    if hearbeat_fee < base_fee {
-        // Minimum fee is base fee
-        return base_fee;
+// Derive order-sensitive asset pair index key from hash(base, quote)
+fn calc_pair_key(e: &Env, base: &TickerAsset, quote: &TickerAsset) -> BytesN<32> {
+    let data = (base.clone(), quote.clone()).to_xdr(e);
+    e.crypto().sha256(&data).into()
+}
+
+// Add subscription to its asset pair index
+fn add_to_pair_index(e: &Env, subscription_id: u64, subscription: &Subscription) {
+    let pair_key = calc_pair_key(e, &subscription.base, &subscription.quote);
+    e.add_pair_index_entry(&pair_key, subscription_id);
+}
+
+// Remove subscription from its asset pair index
+fn remove_from_pair_index(e: &Env, subscription_id: u64, subscription: &Subscription) {
+    let pair_key = calc_pair_key(e, &subscription.base, &subscription.quote);
+    e.remove_pair_index_entry(&pair_key, subscription_id);
+}
+
+// Check whether the asset pair and heartbeat combination is allowlisted as zero-fee
+fn is_free_tier(e: &Env, base: &TickerAsset, quote: &TickerAsset, heartbeat_seconds: u64) -> bool {
+    e.get_free_tier()
+        .iter()
+        .any(|(free_base, free_quote, heartbeat)| {
+            &free_base == base && &free_quote == quote && heartbeat as u64 * 60 == heartbeat_seconds
+        })
+}
+
+// Count a new free tier subscription for the owner
+fn increment_free_tier_count(e: &Env, owner: &Address) {
+    let count = e.get_free_tier_count(owner);
+    if count >= MAX_FREE_TIER_PER_OWNER {
+        e.panic_with_error(Error::FreeTierLimitReached);
     }
-    hearbeat_fee
+    e.set_free_tier_count(owner, count + 1);
+}
+
+// Release a free tier slot of the owner
+fn decrement_free_tier_count(e: &Env, owner: &Address) {
+    e.set_free_tier_count(owner, e.get_free_tier_count(owner).saturating_sub(1));
+}
+
+// Add subscription to the indexes of the oracle sources it depends on
+fn add_to_source_index(e: &Env, subscription_id: u64, subscription: &Subscription) {
+    for source in calc_subscription_sources(e, subscription).iter() {
+        e.add_source_index_entry(&source, subscription_id);
+    }
+}
+
+// Remove subscription from the indexes of the oracle sources it depends on
+fn remove_from_source_index(e: &Env, subscription_id: u64, subscription: &Subscription) {
+    for source in calc_subscription_sources(e, subscription).iter() {
+        e.remove_source_index_entry(&source, subscription_id);
+    }
+}
+
+// Get the cursor for the next page of an index, 0 if the page is the last one
+fn calc_page_cursor(page: &Vec<u64>, limit: u32) -> u64 {
+    if page.len() < limit {
+        return 0;
+    }
+    page.last().unwrap_or(0)
+}
+
+// Get a page of subscription IDs for a read-only scan and the cursor for the next page, 0 once the scan is complete
+// Sequentially allocated IDs are scanned first, followed by the index of derived and remapped IDs
+fn calc_scan_page(e: &Env, start_after_id: u64, limit: u32) -> (Vec<u64>, u64) {
+    let mut subscription_ids = Vec::new(e);
+    if limit == 0 {
+        return (subscription_ids, 0);
+    }
+    let mut remaining = limit.min(MAX_BATCH_SIZE);
+    // Cursors pointing at the derived index continue the second phase
+    let mut derived_cursor = start_after_id;
+    if !e.has_derived_index_entry(start_after_id) {
+        let last_id = calc_last_sequential_id(e);
+        let mut cursor = start_after_id;
+        while cursor < last_id && remaining > 0 {
+            cursor += 1;
+            // Remapped records are scanned with the derived index, so pages never end on them
+            if e.has_derived_index_entry(cursor) {
+                continue;
+            }
+            remaining -= 1;
+            subscription_ids.push_back(cursor);
+        }
+        if remaining == 0 {
+            return (subscription_ids, cursor);
+        }
+        derived_cursor = 0;
+    }
+    let page = e.get_derived_index_page(derived_cursor, remaining);
+    subscription_ids.append(&page);
+    (subscription_ids, calc_page_cursor(&page, remaining))
+}
+
+// Get distinct oracle sources used by the subscription
+fn calc_subscription_sources(e: &Env, subscription: &Subscription) -> Vec<String> {
+    calc_asset_sources(e, &subscription.base, &subscription.quote)
 }
 
-fn calc_complexity_factor(base_symbol: &TickerAsset, quote_symbol: &TickerAsset) -> u64 {
-    if base_symbol.source != quote_symbol.source {
-        return 2; //cross-price
+// Get distinct oracle sources of the asset pair
+fn calc_asset_sources(e: &Env, base: &TickerAsset, quote: &TickerAsset) -> Vec<String> {
+    let mut sources = Vec::from_array(e, [base.source.clone()]);
+    if quote.source != base.source {
+        sources.push_back(quote.source.clone());
     }
-    1
+    sources
+}
+
+// Extend storage TTL of the subscription along with its index entries
+fn extend_subscription_storage_ttl(
+    e: &Env,
+    subscription_id: u64,
+    subscription: &Subscription,
+    extend_to: u32,
+) {
+    e.extend_subscription_ttl(subscription_id, extend_to);
+    e.extend_index_entry_ttls(
+        subscription_id,
+        &calc_pair_key(e, &subscription.base, &subscription.quote),
+        &calc_subscription_sources(e, subscription),
+        &subscription.owner,
+        extend_to,
+    );
+}
+
+// Add subscription to its owner index
+fn add_to_owner_index(e: &Env, subscription_id: u64, owner: &Address) {
+    e.add_owner_index_entry(owner, subscription_id);
+}
+
+// Remove subscription from its owner index
+fn remove_from_owner_index(e: &Env, subscription_id: u64, owner: &Address) {
+    e.remove_owner_index_entry(owner, subscription_id);
 }
 
 // Check that contract has been properly initialized already
 fn panic_if_not_initialized(e: &Env) {
+    panic_if_not_configured(e);
+    // Records stored in the legacy layout can't be decoded until migrated
+    if !is_schema_current(e) {
+        panic_with_error!(e, Error::MigrationRequired);
+    }
+}
+
+// Check whether the storage has been migrated to the current schema version
+fn is_schema_current(e: &Env) -> bool {
+    e.get_schema_version().unwrap_or(0) >= SCHEMA_VERSION
+}
+
+// Check that the contract is initialized, regardless of the storage schema version
+fn panic_if_not_configured(e: &Env) {
     if !e.is_initialized() {
         panic_with_error!(e, Error::NotInitialized);
     }
 }
 
-// Initialize a client for Reflector token contract
-fn get_token_client(e: &Env) -> TokenClient {
-    TokenClient::new(e, &e.get_token())
+// Check that the token address is neither the admin address nor an all-zero address
+fn panic_if_invalid_token(e: &Env, admin: &Address, token: &Address) {
+    if token == admin
+        || token == &Address::from_string(&String::from_str(e, ZERO_ACCOUNT))
+        || token == &Address::from_string(&String::from_str(e, ZERO_CONTRACT))
+    {
+        panic_with_error!(e, Error::InvalidToken);
+    }
+}
+
+// Check that the notification format is supported
+fn panic_if_invalid_format(e: &Env, format: u32) {
+    if format > MAX_FORMAT {
+        panic_with_error!(e, Error::InvalidFormat);
+    }
+}
+
+// Check that the batch doesn't exceed the maximum allowed size
+fn panic_if_too_many_items(e: &Env, len: u32) {
+    if len > MAX_BATCH_SIZE {
+        panic_with_error!(e, Error::TooManyItems);
+    }
+}
+
+// Initialize a client for the given token contract
+fn get_token_client<'a>(e: &'a Env, token: &Address) -> TokenClient<'a> {
+    TokenClient::new(e, token)
+}
+
+// Get the period during which a soft-cancelled subscription can be restored
+fn get_recovery_window(e: &Env) -> u64 {
+    e.get_recovery_window().unwrap_or(DEFAULT_RECOVERY_WINDOW)
+}
+
+// Get the soft cancellation timestamp of a subscription pending deletion
+fn get_pending_deletion_timestamp(e: &Env, subscription: &Subscription) -> u64 {
+    match (subscription.status, subscription.cancelled_at) {
+        (SubscriptionStatus::PendingDeletion, Some(cancelled_at)) => cancelled_at,
+        _ => panic_with_error!(e, Error::InvalidSubscriptionStatusError),
+    }
+}
+
+// Get cached retention token decimals, contracts configured before caching query the token
+fn get_token_decimals(e: &Env) -> u32 {
+    e.get_token_decimals()
+        .unwrap_or_else(|| get_token_client(e, &e.get_token()).decimals())
 }
 
 // Transfer tokens to the contract balance
-fn deposit(e: &Env, from: &Address, amount: u64) {
-    get_token_client(e).transfer(from, &e.current_contract_address(), &(amount as i128));
+fn deposit(e: &Env, token: &Address, from: &Address, amount: u64) {
+    let token = get_token_client(e, token);
+    let contract = e.current_contract_address();
+    let balance_before = token.balance(&contract);
+    token.transfer(from, &contract, &(amount as i128));
+    panic_if_balance_mismatch(e, &token, balance_before, amount as i128);
 }
 
-// Burn used tokens
-fn burn(e: &Env, burn_amount: u64, max_burn: u64) {
+// Burn used tokens, the treasury share is transferred to the treasury instead
+fn burn(e: &Env, token: &Address, burn_amount: u64, max_burn: u64) {
     if burn_amount > max_burn {
         panic_with_error!(e, Error::InvalidAmount);
     }
-    get_token_client(e).burn(&e.current_contract_address(), &(burn_amount as i128));
+    // Route the treasury share
+    let mut burn_amount = burn_amount;
+    if let Some(treasury) = e.get_treasury() {
+        let treasury_amount = calc_treasury_share(e, burn_amount);
+        if treasury_amount > 0 {
+            withdraw(e, token, &treasury, treasury_amount);
+            burn_amount -= treasury_amount;
+        }
+    }
+    burn_tokens(e, token, burn_amount);
+}
+
+// Calculate the part of used tokens routed to the treasury, rounding down in favor of the burn
+fn calc_treasury_share(e: &Env, amount: u64) -> u64 {
+    if e.get_treasury().is_none() {
+        return 0;
+    }
+    (amount as u128 * e.get_treasury_share_bps() as u128 / 10000) as u64
+}
+
+// Burn tokens from the contract balance
+fn burn_tokens(e: &Env, token: &Address, amount: u64) {
+    if amount == 0 {
+        return;
+    }
+    let token = get_token_client(e, token);
+    let contract = e.current_contract_address();
+    let balance_before = token.balance(&contract);
+    token.burn(&contract, &(amount as i128));
+    panic_if_balance_mismatch(e, &token, balance_before, -(amount as i128));
 }
 
 // Withdraw tokens from contract balance
-fn withdraw(e: &Env, to: &Address, amount: u64) {
-    get_token_client(e).transfer(&e.current_contract_address(), to, &(amount as i128));
+fn withdraw(e: &Env, token: &Address, to: &Address, amount: u64) {
+    let token = get_token_client(e, token);
+    let contract = e.current_contract_address();
+    let balance_before = token.balance(&contract);
+    token.transfer(&contract, to, &(amount as i128));
+    panic_if_balance_mismatch(e, &token, balance_before, -(amount as i128));
+}
+
+// Verify that the contract token balance changed exactly by the expected amount
+// Protects from non-standard tokens that silently fail or under-transfer
+fn panic_if_balance_mismatch(e: &Env, token: &TokenClient, balance_before: i128, change: i128) {
+    let expected = balance_before.checked_add(change).unwrap();
+    if token.balance(&e.current_contract_address()) != expected {
+        panic_with_error!(e, Error::TokenTransferFailed);
+    }
+}
+
+// Parse major version number from the version string, skipping any non-numeric prefix
+fn parse_major_version(version: &str) -> u32 {
+    let mut major: u32 = 0;
+    for c in version.chars().skip_while(|c| !c.is_ascii_digit()) {
+        match c.to_digit(10) {
+            Some(digit) => major = major.saturating_mul(10).saturating_add(digit),
+            None => break,
+        }
+    }
+    major
 }
 
 // Get timestamp as milliseconds
@@ -580,22 +4939,83 @@ fn now(e: &Env) -> u64 {
 }
 
 // Calculate number of ledgers to live for subscription based on retention fee
+// The result is capped at the maximum TTL, the balance is kept intact and the TTL can be extended later
 fn calc_ledgers_to_live(e: &Env, fee: u64, amount: u64) -> u32 {
-    let mut days: u32 = amount
+    // Subscriptions that are not charged are kept alive as long as possible
+    if fee == 0 {
+        return e.storage().max_ttl();
+    }
+    let days = amount
         .checked_add(fee)
         .unwrap()
         .checked_sub(1)
         .unwrap()
         .checked_div(fee)
-        .unwrap() as u32;
-    if days == 0 {
-        days = 1;
+        .unwrap()
+        .max(1);
+    days.saturating_mul(17280).min(e.storage().max_ttl() as u64) as u32
+}
+
+// Calculate the creation fee multiplier as the highest multiplier of the pair asset tiers
+fn calc_init_multiplier(e: &Env, base: &TickerAsset, quote: &TickerAsset) -> u32 {
+    let mut multiplier: Option<u32> = None;
+    for (asset, asset_multiplier) in e.get_asset_tiers().iter() {
+        if &asset == base || &asset == quote {
+            multiplier = Some(multiplier.unwrap_or(0).max(asset_multiplier));
+        }
     }
-    let ledgers = days.checked_mul(17280).unwrap();
-    if ledgers > e.storage().max_ttl() {
-        panic_with_error!(e, Error::InvalidAmount);
+    multiplier.unwrap_or(DEFAULT_INIT_MULTIPLIER)
+}
+
+// Calculate the creation fee of a pair from the daily retention fee
+fn calc_init_fee(e: &Env, base: &TickerAsset, quote: &TickerAsset, retention_fee: u64) -> u64 {
+    retention_fee
+        .checked_mul(calc_init_multiplier(e, base, quote) as u64)
+        .unwrap()
+}
+
+// Calculate the part of the initial deposit taken as the creation fee and the daily retention fee
+fn calc_creation_costs(e: &Env, new_subscription: &SubscriptionInitParams) -> (u64, u64) {
+    let heartbeat_seconds = calc_heartbeat_seconds(
+        resolve_heartbeat(e, new_subscription),
+        new_subscription.heartbeat_unit,
+    );
+    // Allowlisted combinations are not charged at all
+    if is_free_tier(
+        e,
+        &new_subscription.base,
+        &new_subscription.quote,
+        heartbeat_seconds,
+    ) {
+        return (0, 0);
     }
-    ledgers
+    let retention_fee = calc_contract_fee(
+        e,
+        &new_subscription.base,
+        &new_subscription.quote,
+        heartbeat_seconds,
+    );
+    // Creation fee is paid separately when the init token is set
+    let init_cost = if e.get_init_token().is_some() {
+        0
+    } else {
+        calc_init_fee(
+            e,
+            &new_subscription.base,
+            &new_subscription.quote,
+            retention_fee,
+        )
+    };
+    (init_cost, retention_fee)
+}
+
+// Calculate the storage TTL of a stored subscription
+fn calc_subscription_ttl(e: &Env, subscription: &Subscription) -> u32 {
+    calc_ledgers_to_live(
+        e,
+        calc_subscription_fee(e, subscription),
+        subscription.balance,
+    )
 }
 
 fn publish_updated_event<T>(e: &Env, sub_topic: &Symbol, data: T)
@@ -1,16 +1,61 @@
 #![cfg(test)]
+extern crate std;
 
 use super::*;
 use soroban_sdk::{
-    testutils::{storage::Persistent, Address as _, Ledger, LedgerInfo},
+    testutils::{
+        storage::Persistent, Address as _, AuthorizedFunction, AuthorizedInvocation, Events,
+        Ledger, LedgerInfo, MockAuth, MockAuthInvoke,
+    },
     token::StellarAssetClient,
-    vec, Bytes, Env, String,
+    vec, Bytes, Env, String, TryFromVal,
 };
 use types::{
-    contract_config::ContractConfig, subscription_init_params::SubscriptionInitParams,
-    ticker_asset::TickerAsset,
+    contract_config::ContractConfig, heartbeat_unit::HeartbeatUnit,
+    subscription_init_params::SubscriptionInitParams, threshold_kind::ThresholdKind,
+    threshold_param::ThresholdParam, ticker_asset::TickerAsset,
 };
 
+// Token that silently transfers less than requested
+mod under_transfer_token {
+    use soroban_sdk::{contract, contractimpl, Address, Env};
+
+    #[contract]
+    pub struct UnderTransferToken;
+
+    #[contractimpl]
+    impl UnderTransferToken {
+        pub fn mint(e: Env, to: Address, amount: i128) {
+            let balance = Self::balance(e.clone(), to.clone());
+            e.storage().instance().set(&to, &(balance + amount));
+        }
+
+        pub fn balance(e: Env, id: Address) -> i128 {
+            e.storage().instance().get(&id).unwrap_or(0)
+        }
+
+        pub fn transfer(e: Env, from: Address, to: Address, amount: i128) {
+            from.require_auth();
+            let transferred = amount - 1;
+            let from_balance = Self::balance(e.clone(), from.clone());
+            e.storage()
+                .instance()
+                .set(&from, &(from_balance - transferred));
+            Self::mint(e, to, transferred);
+        }
+
+        pub fn burn(e: Env, from: Address, amount: i128) {
+            from.require_auth();
+            let balance = Self::balance(e.clone(), from.clone());
+            e.storage().instance().set(&from, &(balance - amount));
+        }
+
+        pub fn decimals(_e: Env) -> u32 {
+            7
+        }
+    }
+}
+
 fn init_contract_with_admin<'a>() -> (Env, SubscriptionContractClient<'a>, ContractConfig) {
     let env = Env::default();
 
@@ -25,6 +70,8 @@ fn init_contract_with_admin<'a>() -> (Env, SubscriptionContractClient<'a>, Contr
     let init_data = ContractConfig {
         admin: admin.clone(),
         token: token.address(),
+        init_token: None,
+        features: Vec::new(&env),
         fee: 100000000,
     };
 
@@ -36,6 +83,62 @@ fn init_contract_with_admin<'a>() -> (Env, SubscriptionContractClient<'a>, Contr
     (env, client, init_data)
 }
 
+fn generate_subscription_params(env: &Env, owner: &Address) -> SubscriptionInitParams {
+    SubscriptionInitParams {
+        owner: owner.clone(),
+        base: TickerAsset {
+            asset: String::from_str(env, "BTC"),
+            source: String::from_str(env, "source1"),
+        },
+        quote: TickerAsset {
+            asset: String::from_str(env, "ETH"),
+            source: String::from_str(env, "source2"),
+        },
+        threshold: ThresholdParam::Custom(ThresholdKind::Relative(10)),
+        heartbeat: Some(5),
+        heartbeat_unit: HeartbeatUnit::Minutes,
+        webhook: Bytes::from_array(env, &[0; 64]),
+        reserve: 0,
+        id_nonce: None,
+        format: 0,
+        expires_at: None,
+        active_from_minute: None,
+        active_to_minute: None,
+    }
+}
+
+fn generate_funded_owner(env: &Env, config: &ContractConfig) -> Address {
+    let owner = Address::generate(env);
+    StellarAssetClient::new(env, &config.token).mint(&owner, &(config.fee * 1000).into());
+    owner
+}
+
+fn contract_error(error: Error) -> soroban_sdk::Error {
+    soroban_sdk::Error::from_contract_error(error as u32)
+}
+
+// Count events published by the contract with the given event name topic
+fn count_events(env: &Env, contract_id: &Address, name: Symbol) -> u32 {
+    let mut count = 0;
+    for (address, topics, _) in env.events().all().iter() {
+        if &address != contract_id || topics.len() < 3 {
+            continue;
+        }
+        if Symbol::try_from_val(env, &topics.get_unchecked(2)) == Ok(name.clone()) {
+            count += 1;
+        }
+    }
+    count
+}
+
+fn set_timestamp(env: &Env, timestamp: u64) {
+    let ledger_info = env.ledger().get();
+    env.ledger().set(LedgerInfo {
+        timestamp,
+        ..ledger_info
+    });
+}
+
 #[test]
 fn test() {
     let (env, client, config) = init_contract_with_admin();
@@ -55,20 +158,27 @@ fn test() {
             asset: String::from_str(&env, "ETH"),
             source: String::from_str(&env, "source2"),
         },
-        threshold: 10,
-        heartbeat: 5,
+        threshold: ThresholdParam::Custom(ThresholdKind::Relative(10)),
+        heartbeat: Some(5),
+        heartbeat_unit: HeartbeatUnit::Minutes,
         webhook: Bytes::from_array(&env, &[0; 2048]),
+        reserve: 0,
+        id_nonce: None,
+        format: 0,
+        expires_at: None,
+        active_from_minute: None,
+        active_to_minute: None,
     };
 
     let fee = calc_fee(
         config.fee,
         &subscription.base,
         &subscription.quote,
-        subscription.heartbeat,
+        subscription.heartbeat.unwrap(),
     );
 
     // create subscription
-    let (subscription_id, _) = client.create_subscription(&subscription, &(fee * 2));
+    let (subscription_id, _) = client.create_subscription(&subscription, &(fee * 2), &None);
     assert!(subscription_id == 1);
 
     env.as_contract(&client.address, || {
@@ -78,10 +188,10 @@ fn test() {
 
     let trigger_hash: BytesN<32> = BytesN::from_array(&env, &[0; 32]);
     // heartbeat subscription
-    client.trigger(&1u64, &trigger_hash);
+    client.trigger(&config.admin, &1u64, &trigger_hash);
 
     // deposit subscription
-    client.deposit(&owner, &1, &fee);
+    client.deposit(&owner, &1, &fee, &None);
 
     env.as_contract(&client.address, || {
         let ttl = env.storage().persistent().get_ttl(&subscription_id);
@@ -98,7 +208,7 @@ fn test() {
     });
 
     // charge subscription
-    client.charge(&vec![&env, 1u64]);
+    client.charge(&config.admin, &vec![&env, 1u64]);
 
     // check balance and status
     subs = client.get_subscription(&subscription_id);
@@ -107,13 +217,13 @@ fn test() {
     assert_eq!(subs.updated, 86400 * 2 * 1000);
 
     // deposit subscription to renew
-    client.deposit(&owner, &1, &(fee * 2));
+    client.deposit(&owner, &1, &(fee * 2), &None);
     subs = client.get_subscription(&subscription_id);
     assert_eq!(subs.balance, fee); // deposit amount - activation fee
     assert_eq!(subs.status, SubscriptionStatus::Active);
 
     // cancel subscription
-    client.cancel(&1u64);
+    client.cancel(&1u64, &false);
     env.as_contract(&client.address, || {
         let subs = env.get_subscription(subscription_id);
         assert_eq!(subs, None);
@@ -127,7 +237,6 @@ fn test() {
         let current_fee = env.get_fee();
         assert_eq!(current_fee, fee * 2);
     });
-
 }
 
 #[test]
@@ -182,3 +291,4626 @@ fn fee_test() {
         );
     }
 }
+
+#[test]
+fn deterministic_id_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    params.id_nonce = Some(42);
+
+    let expected_id = client.derive_subscription_id(&owner, &params.base, &params.quote, &42u64);
+    let amount = config.fee * 100;
+    let (subscription_id, _) = client.create_subscription(&params, &amount, &None);
+    assert_eq!(subscription_id, expected_id);
+    assert_eq!(client.get_subscription(&subscription_id).owner, owner);
+    // Sequential counter is not affected
+    assert_eq!(client.last_id(), 0);
+
+    // Same owner, pair and nonce collide with the existing subscription
+    let result = client.try_create_subscription(&params, &amount, &None);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::IdCollision))));
+
+    // Sequential IDs are still allocated by default
+    params.id_nonce = None;
+    let (subscription_id, _) = client.create_subscription(&params, &amount, &None);
+    assert_eq!(subscription_id, 1);
+}
+
+#[test]
+fn charge_as_of_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, subscription) = client.create_subscription(&params, &(fee * 12), &None);
+    assert_eq!(subscription.balance, fee * 10);
+
+    set_timestamp(&env, 86400 * 5);
+
+    // Timestamps from the future are rejected
+    let result = client.try_charge_as_of(&vec![&env, subscription_id], &(86400 * 6 * 1000));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimestamp)))
+    );
+
+    // Settle only the first two days of the period
+    client.charge_as_of(&vec![&env, subscription_id], &(86400 * 2 * 1000));
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.balance, fee * 8);
+    assert_eq!(subscription.last_charged, 86400 * 2 * 1000);
+    assert_eq!(subscription.updated, 86400 * 5 * 1000);
+
+    // Timestamps before the last settlement are rejected
+    let result = client.try_charge_as_of(&vec![&env, subscription_id], &(86400 * 1000));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimestamp)))
+    );
+
+    // Regular charge settles the remaining days
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.balance, fee * 5);
+    assert_eq!(subscription.last_charged, 86400 * 5 * 1000);
+}
+
+#[test]
+fn reserve_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let token = TokenClient::new(&env, &config.token);
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+
+    // Reserve can't exceed the initial balance
+    params.reserve = fee * 11;
+    let result = client.try_create_subscription(&params, &(fee * 12), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidReserve)))
+    );
+
+    params.reserve = fee * 4;
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 12), &None);
+
+    // Charge more days than the balance above the reserve can cover
+    set_timestamp(&env, 86400 * 8);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.balance, fee * 4);
+    assert_eq!(subscription.status, SubscriptionStatus::Suspended);
+
+    // Reserve can't be raised above the balance
+    let result = client.try_set_reserve(&subscription_id, &(fee * 5));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidReserve)))
+    );
+
+    // Suspended subscription can be cancelled and the reserve is refunded
+    let owner_balance = token.balance(&owner);
+    client.cancel(&subscription_id, &false);
+    assert_eq!(token.balance(&owner), owner_balance + (fee * 4) as i128);
+}
+
+#[test]
+fn fee_curve_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let params = generate_subscription_params(&env, &Address::generate(&env));
+    let heartbeats = vec![&env, 5u32, 10, 30, 60, 120, 240, 1440];
+    let curve = client.fee_curve(&params.base, &params.quote, &heartbeats);
+    assert_eq!(curve.len(), heartbeats.len());
+    for (i, heartbeat) in heartbeats.iter().enumerate() {
+        let fee = curve.get_unchecked(i as u32);
+        assert_eq!(
+            fee,
+            calc_fee(config.fee, &params.base, &params.quote, heartbeat)
+        );
+        if i > 0 {
+            // Fee never grows with the heartbeat
+            assert!(fee <= curve.get_unchecked(i as u32 - 1));
+        }
+    }
+
+    // Heartbeats below the minimum are rejected
+    let result = client.try_fee_curve(&params.base, &params.quote, &vec![&env, 60u32, 1]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidHeartbeat)))
+    );
+
+    // Number of heartbeats is capped at the batch size
+    let mut heartbeats = Vec::new(&env);
+    for _ in 0..20 {
+        heartbeats.push_back(60u32);
+    }
+    assert_eq!(
+        client
+            .fee_curve(&params.base, &params.quote, &heartbeats)
+            .len(),
+        20
+    );
+    heartbeats.push_back(60);
+    let result = client.try_fee_curve(&params.base, &params.quote, &heartbeats);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+}
+
+#[test]
+fn token_transfer_mismatch_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let token = env.register_contract(None, under_transfer_token::UnderTransferToken);
+    let token_client = under_transfer_token::UnderTransferTokenClient::new(&env, &token);
+
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    client.config(&ContractConfig {
+        admin,
+        token: token.clone(),
+        init_token: None,
+        features: Vec::new(&env),
+        fee: 100,
+    });
+
+    let owner = Address::generate(&env);
+    token_client.mint(&owner, &1_000_000);
+
+    let params = generate_subscription_params(&env, &owner);
+    let result = client.try_create_subscription(&params, &100_000, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::TokenTransferFailed)))
+    );
+    // Nothing has been transferred
+    assert_eq!(token_client.balance(&owner), 1_000_000);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+fn subscription_token_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let (subscription_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+
+    assert_eq!(client.subscription_token(&subscription_id), config.token);
+    let result = client.try_subscription_token(&(subscription_id + 1));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+}
+
+#[test]
+fn charge_events_opt_out_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (first_id, subscription) = client.create_subscription(&params, &(fee * 10), &None);
+    assert!(subscription.emit_charge_events);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    client.set_event_prefs(&second_id, &false);
+    assert!(!client.get_subscription(&second_id).emit_charge_events);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, first_id, second_id]);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("charged")),
+        1
+    );
+
+    // Both subscriptions have been charged
+    assert_eq!(client.get_subscription(&first_id).balance, fee * 7);
+    assert_eq!(client.get_subscription(&second_id).balance, fee * 7);
+}
+
+#[test]
+fn heartbeat_seconds_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let minutes_params = generate_subscription_params(&env, &owner);
+    let mut seconds_params = minutes_params.clone();
+    seconds_params.heartbeat = Some(300);
+    seconds_params.heartbeat_unit = HeartbeatUnit::Seconds;
+
+    // Equivalent minute and second specifications are priced the same
+    for (minutes, seconds) in [(5u32, 300u64), (7, 420), (120, 7200), (1440, 86400)] {
+        assert_eq!(
+            calc_fee(
+                config.fee,
+                &minutes_params.base,
+                &minutes_params.quote,
+                minutes
+            ),
+            calc_fee_seconds(
+                config.fee,
+                &minutes_params.base,
+                &minutes_params.quote,
+                seconds
+            )
+        );
+    }
+
+    let (minutes_id, _) = client.create_subscription(&minutes_params, &(config.fee * 100), &None);
+    let (seconds_id, subscription) =
+        client.create_subscription(&seconds_params, &(config.fee * 100), &None);
+    assert_eq!(subscription.heartbeat_unit, HeartbeatUnit::Seconds);
+    assert_eq!(
+        client.get_retention_fee(&minutes_id),
+        client.get_retention_fee(&seconds_id)
+    );
+
+    // Sub-minute heartbeat costs more than the minimum minute heartbeat
+    seconds_params.heartbeat = Some(30);
+    let (sub_minute_id, _) =
+        client.create_subscription(&seconds_params, &(config.fee * 100), &None);
+    assert!(client.get_retention_fee(&sub_minute_id) > client.get_retention_fee(&minutes_id));
+
+    // Heartbeat below the minimum in seconds is rejected
+    seconds_params.heartbeat = Some(MIN_HEARTBEAT_SECONDS - 1);
+    let result = client.try_create_subscription(&seconds_params, &(config.fee * 500), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidHeartbeat)))
+    );
+}
+
+#[test]
+fn is_owner_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let (subscription_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+
+    assert!(client.is_owner(&subscription_id, &owner));
+    assert!(!client.is_owner(&subscription_id, &Address::generate(&env)));
+    assert!(!client.is_owner(&(subscription_id + 1), &owner));
+}
+
+#[test]
+fn trigger_subscriptions_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let (first_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+    let (second_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+
+    let first_leaf = BytesN::from_array(&env, &[1; 32]);
+    let second_leaf = BytesN::from_array(&env, &[2; 32]);
+    let leaves = vec![
+        &env,
+        (first_id, first_leaf.clone()),
+        (second_id, second_leaf.clone()),
+        (second_id + 1, first_leaf.clone()), // missing subscriptions are skipped
+    ];
+    client.trigger_subscriptions(&config.admin, &1000u64, &leaves);
+
+    let mut decoded = Vec::<(u64, u64, BytesN<32>)>::new(&env);
+    for (address, topics, data) in env.events().all().iter() {
+        if address != client.address || topics.len() != 4 {
+            continue;
+        }
+        let name = Symbol::try_from_val(&env, &topics.get_unchecked(2)).unwrap();
+        if name != symbol_short!("triggered") {
+            continue;
+        }
+        let topic_owner = Address::try_from_val(&env, &topics.get_unchecked(3)).unwrap();
+        assert_eq!(topic_owner, owner);
+        decoded.push_back(<(u64, u64, BytesN<32>)>::try_from_val(&env, &data).unwrap());
+    }
+    assert_eq!(
+        decoded,
+        vec![
+            &env,
+            (first_id, 1000u64, first_leaf),
+            (second_id, 1000u64, second_leaf)
+        ]
+    );
+
+    client.trigger_subscriptions(
+        &config.admin,
+        &2000u64,
+        &vec![&env, (first_id, BytesN::from_array(&env, &[3; 32]))],
+    );
+    assert_eq!(client.get_subscription(&first_id).trigger_count, 2);
+    assert_eq!(client.get_subscription(&second_id).trigger_count, 1);
+
+    // Oversized batches are rejected
+    let mut leaves = Vec::new(&env);
+    for _ in 0..=MAX_BATCH_SIZE {
+        leaves.push_back((first_id, BytesN::from_array(&env, &[0; 32])));
+    }
+    let result = client.try_trigger_subscriptions(&config.admin, &3000u64, &leaves);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+}
+
+#[test]
+fn partial_initialization_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+
+    // Simulate a contract with only some of the configuration keys written
+    env.as_contract(&contract_id, || {
+        env.set_admin(&admin);
+        env.set_fee(100);
+        assert!(!env.is_initialized());
+    });
+    let result = client.try_fee();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::NotInitialized)))
+    );
+
+    env.as_contract(&contract_id, || {
+        env.set_token(&token.address());
+        assert!(!env.is_initialized());
+        env.set_last_subscription_id(0);
+        assert!(env.is_initialized());
+    });
+    // Storage without the schema version has to be migrated first
+    let result = client.try_fee();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::MigrationRequired)))
+    );
+    client.migrate(&0, &10);
+    assert_eq!(client.fee(), 100);
+}
+
+#[test]
+fn subscriptions_at_risk_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    // Runway of 1, 10, 3 and 20 days
+    for days in [1u64, 10, 3, 20] {
+        client.create_subscription(&params, &(fee * (days + 2)), &None);
+    }
+
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &0, &10),
+        (vec![&env, 1u64, 3], 0)
+    );
+    assert_eq!(
+        client.subscriptions_at_risk(&15, &0, &10),
+        (vec![&env, 1u64, 2, 3], 0)
+    );
+    // Paginate with the returned cursor
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &0, &2),
+        (vec![&env, 1u64], 2)
+    );
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &2, &2),
+        (vec![&env, 3u64], 4)
+    );
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &4, &2),
+        (Vec::<u64>::new(&env), 0)
+    );
+    // Zero limit doesn't return a cursor to loop on
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &2, &0),
+        (Vec::<u64>::new(&env), 0)
+    );
+
+    // Subscriptions with derived IDs are scanned after the sequential range
+    let mut nonce_params = params.clone();
+    nonce_params.id_nonce = Some(1);
+    let (derived_id, _) = client.create_subscription(&nonce_params, &(fee * 3), &None);
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &0, &10),
+        (vec![&env, 1u64, 3, derived_id], 0)
+    );
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &4, &1),
+        (vec![&env, derived_id], derived_id)
+    );
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &derived_id, &1),
+        (Vec::<u64>::new(&env), 0)
+    );
+
+    // Suspended subscriptions are not included
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, 1u64]);
+    assert_eq!(
+        client.subscriptions_at_risk(&5, &0, &10),
+        (vec![&env, 3u64, derived_id], 0)
+    );
+}
+
+#[test]
+fn cancel_refund_estimate_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let token = TokenClient::new(&env, &config.token);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    set_timestamp(&env, 86400 * 3);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+
+    let estimate = client.cancel_refund_estimate(&subscription_id);
+    assert_eq!(estimate, fee * 5);
+
+    let owner_balance = token.balance(&owner);
+    client.cancel(&subscription_id, &false);
+    assert_eq!(token.balance(&owner), owner_balance + estimate as i128);
+}
+
+#[test]
+fn defer_burns_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let token = TokenClient::new(&env, &config.token);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    assert!(!client.defer_burns());
+
+    client.set_defer_burns(&true);
+    assert!(client.defer_burns());
+
+    let contract_balance = token.balance(&client.address);
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    set_timestamp(&env, 86400 * 3);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+
+    // Balances are deducted, but tokens are kept on the contract
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 5);
+    assert_eq!(client.pending_burn(), fee * 3);
+    assert_eq!(token.balance(&client.address), contract_balance);
+
+    client.flush_burns();
+    assert_eq!(client.pending_burn(), 0);
+    assert_eq!(
+        token.balance(&client.address),
+        contract_balance - (fee * 3) as i128
+    );
+
+    // Charges are burned right away once deferring is disabled
+    client.set_defer_burns(&false);
+    set_timestamp(&env, 86400 * 4);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(client.pending_burn(), 0);
+    assert_eq!(
+        token.balance(&client.address),
+        contract_balance - (fee * 4) as i128
+    );
+}
+
+#[test]
+fn deferred_burn_stats_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let token = TokenClient::new(&env, &config.token);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    assert_eq!(client.fees_burned_between(&0, &10), fee * 2);
+
+    // Deferred charges are not counted as burned
+    client.set_defer_burns(&true);
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(client.pending_burn(), fee * 2);
+    assert_eq!(client.fees_burned_between(&0, &10), fee * 2);
+
+    // Flushed fees are counted with the treasury share applied at the flush time
+    let treasury = Address::generate(&env);
+    client.set_treasury(&Some(treasury.clone()), &5000);
+    set_timestamp(&env, 86400 * 3);
+    client.flush_burns();
+    assert_eq!(token.balance(&treasury), fee as i128);
+    assert_eq!(client.fees_burned_between(&2, &2), 0);
+    assert_eq!(client.fees_burned_between(&3, &3), fee);
+    assert_eq!(client.fees_burned_between(&0, &10), fee * 3);
+}
+
+#[test]
+fn trigger_interval_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let trigger_hash = BytesN::from_array(&env, &[0; 32]);
+
+    // No limit by default
+    client.trigger(&config.admin, &1u64, &trigger_hash);
+    client.trigger(&config.admin, &2u64, &trigger_hash);
+
+    client.set_trigger_interval(&60_000);
+    assert_eq!(client.trigger_interval(), 60_000);
+
+    set_timestamp(&env, 30);
+    let result = client.try_trigger(&config.admin, &3u64, &trigger_hash);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::TriggerTooSoon)))
+    );
+
+    set_timestamp(&env, 60);
+    client.trigger(&config.admin, &4u64, &trigger_hash);
+    let result = client.try_trigger(&config.admin, &5u64, &trigger_hash);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::TriggerTooSoon)))
+    );
+}
+
+#[test]
+fn subscription_economics_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 5), &None);
+    client.deposit(&owner, &subscription_id, &(fee * 2 + fee / 2), &None);
+
+    let (retention_fee, runway_days, revival_fee, ttl_ledgers) =
+        client.subscription_economics(&subscription_id);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(retention_fee, client.get_retention_fee(&subscription_id));
+    assert_eq!(runway_days as u64, subscription.balance / retention_fee);
+    assert_eq!(runway_days, 5);
+    assert_eq!(revival_fee, retention_fee);
+    env.as_contract(&client.address, || {
+        let ttl = env.storage().persistent().get_ttl(&subscription_id);
+        assert_eq!(ttl_ledgers, ttl);
+    });
+}
+
+#[test]
+fn protocol_version_test() {
+    let (_, client, _) = init_contract_with_admin();
+
+    assert_eq!(
+        client.version(),
+        parse_major_version(env!("CARGO_PKG_VERSION"))
+    );
+
+    // Upgrade tooling updates the stored protocol version
+    client.set_protocol_version(&2);
+    assert_eq!(client.version(), 2);
+
+    assert_eq!(parse_major_version("1.0.2"), 1);
+    assert_eq!(parse_major_version("12.3.0"), 12);
+    assert_eq!(parse_major_version("0-rc.1"), 0);
+    assert_eq!(parse_major_version("v3.1.0"), 3);
+    assert_eq!(parse_major_version("rc"), 0);
+}
+
+#[test]
+fn clone_subscription_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let (subscription_id, source) = client.create_subscription(&params, &(config.fee * 100), &None);
+
+    let (clone_id, clone) =
+        client.clone_subscription(&subscription_id, &(config.fee * 50), &Some(120));
+    assert_eq!(clone_id, subscription_id + 1);
+    assert_eq!(clone.owner, source.owner);
+    assert_eq!(clone.base, source.base);
+    assert_eq!(clone.quote, source.quote);
+    assert_eq!(clone.webhook, source.webhook);
+    assert_eq!(clone.heartbeat, 120);
+    // Lower cadence results in a lower fee
+    let clone_fee = calc_fee(config.fee, &params.base, &params.quote, 120);
+    assert_eq!(client.get_retention_fee(&clone_id), clone_fee);
+    assert!(clone_fee < client.get_retention_fee(&subscription_id));
+    assert_eq!(clone.balance, config.fee * 50 - clone_fee * 2);
+
+    // Clone goes through the regular validation
+    let result = client.try_clone_subscription(&subscription_id, &(config.fee * 50), &Some(1));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidHeartbeat)))
+    );
+}
+
+#[test]
+fn threshold_kind_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+
+    params.threshold = ThresholdParam::Custom(ThresholdKind::Relative(25));
+    let (relative_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+    params.threshold = ThresholdParam::Custom(ThresholdKind::Absolute(100_0000000));
+    let (absolute_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+
+    assert_eq!(
+        client.get_subscription(&relative_id).threshold,
+        ThresholdKind::Relative(25)
+    );
+    assert_eq!(
+        client.get_subscription(&absolute_id).threshold,
+        ThresholdKind::Absolute(100_0000000)
+    );
+
+    for threshold in [
+        ThresholdKind::Relative(0),
+        ThresholdKind::Relative(10001),
+        ThresholdKind::Absolute(0),
+    ] {
+        params.threshold = ThresholdParam::Custom(threshold);
+        let result = client.try_create_subscription(&params, &(config.fee * 100), &None);
+        assert_eq!(
+            result.err(),
+            Some(Ok(contract_error(Error::InvalidThreshold)))
+        );
+    }
+}
+
+#[test]
+fn worker_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    let worker = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    assert_eq!(client.worker(), None);
+
+    // Only admin and worker can charge
+    set_timestamp(&env, 86400);
+    let result = client.try_charge(&worker, &vec![&env, subscription_id]);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::Unauthorized))));
+
+    client.set_worker(&Some(worker.clone()));
+    assert_eq!(client.worker(), Some(worker.clone()));
+    client.charge(&worker, &vec![&env, subscription_id]);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 7);
+    client.trigger(&worker, &1u64, &BytesN::from_array(&env, &[0; 32]));
+
+    let result = client.try_charge(&stranger, &vec![&env, subscription_id]);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::Unauthorized))));
+
+    // Worker can't change the fee
+    env.mock_auths(&[MockAuth {
+        address: &worker,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "set_fee",
+            args: (fee * 2,).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_set_fee(&(fee * 2)).is_err());
+    env.mock_all_auths();
+    assert_eq!(client.fee(), config.fee);
+
+    // Removed worker loses access
+    client.set_worker(&None);
+    let result = client.try_charge(&worker, &vec![&env, subscription_id]);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::Unauthorized))));
+}
+
+#[test]
+fn charge_batch_limit_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    set_timestamp(&env, 86400);
+    let mut subscription_ids = vec![&env, subscription_id];
+    for id in 0..MAX_BATCH_SIZE as u64 {
+        subscription_ids.push_back(id + 100);
+    }
+    let result = client.try_charge(&config.admin, &subscription_ids);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+    // Nothing has been charged
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 8);
+
+    subscription_ids.pop_back();
+    client.charge(&config.admin, &subscription_ids);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 7);
+}
+
+#[test]
+fn last_charged_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+
+    // Deposit in the middle of the billing day doesn't reset the billing clock
+    set_timestamp(&env, 86400 + 43200);
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.updated, (86400 + 43200) * 1000);
+    assert_eq!(subscription.last_charged, 86400 * 1000);
+
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.balance, fee * 7);
+    assert_eq!(subscription.last_charged, 86400 * 2 * 1000);
+    assert_eq!(subscription.updated, 86400 * 2 * 1000);
+}
+
+#[test]
+fn suspended_days_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(client.suspended_days(&subscription_id), 0);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Suspended);
+    assert_eq!(subscription.suspended_at, Some(86400 * 1000));
+    assert_eq!(client.suspended_days(&subscription_id), 0);
+
+    set_timestamp(&env, 86400 * 4 + 3600);
+    assert_eq!(client.suspended_days(&subscription_id), 3);
+
+    // Revived subscription is no longer suspended
+    client.deposit(&owner, &subscription_id, &(fee * 3), &None);
+    assert_eq!(client.get_subscription(&subscription_id).suspended_at, None);
+    assert_eq!(client.suspended_days(&subscription_id), 0);
+}
+
+#[test]
+fn init_token_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    let token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let init_token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+
+    // Init token must differ from the retention token
+    let result = client.try_config(&ContractConfig {
+        admin: admin.clone(),
+        token: token.clone(),
+        init_token: Some(token.clone()),
+        features: Vec::new(&env),
+        fee: 100,
+    });
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidToken))));
+
+    let config = ContractConfig {
+        admin: admin.clone(),
+        token: token.clone(),
+        init_token: Some(init_token.clone()),
+        features: Vec::new(&env),
+        fee: 100,
+    };
+    client.config(&config);
+    assert_eq!(client.token(), token);
+    assert_eq!(client.init_token(), init_token);
+
+    let owner = Address::generate(&env);
+    StellarAssetClient::new(&env, &token).mint(&owner, &100000);
+    StellarAssetClient::new(&env, &init_token).mint(&owner, &100000);
+    let token_client = TokenClient::new(&env, &token);
+    let init_token_client = TokenClient::new(&env, &init_token);
+
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    // Retention balance must cover at least one day
+    let result = client.try_create_subscription(&params, &(fee - 1), &None);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+
+    let (subscription_id, subscription) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Creation fee is burned in the init token, the whole amount goes to the retention balance
+    assert_eq!(subscription.balance, fee * 3);
+    assert_eq!(
+        init_token_client.balance(&owner),
+        100000 - (fee * 2) as i128
+    );
+    assert_eq!(init_token_client.balance(&contract_id), 0);
+    assert_eq!(token_client.balance(&owner), 100000 - (fee * 3) as i128);
+    assert_eq!(token_client.balance(&contract_id), (fee * 3) as i128);
+
+    // Deposits and charges use the retention token only
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(token_client.balance(&contract_id), (fee * 4) as i128);
+    set_timestamp(&env, 86400);
+    client.charge(&admin, &vec![&env, subscription_id]);
+    assert_eq!(token_client.balance(&contract_id), (fee * 3) as i128);
+    assert_eq!(
+        init_token_client.balance(&owner),
+        100000 - (fee * 2) as i128
+    );
+    // Creation fees burned in the init token are not counted in the retention token statistics
+    assert_eq!(client.fees_burned_between(&0, &10), fee);
+}
+
+#[test]
+fn subscription_count_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    assert_eq!(client.subscription_count(), 0);
+
+    let (first_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    client.create_subscription(&params, &(fee * 3), &None);
+    let mut nonce_params = params.clone();
+    nonce_params.id_nonce = Some(1);
+    client.create_subscription(&nonce_params, &(fee * 3), &None);
+    assert_eq!(client.subscription_count(), 3);
+    assert_eq!(client.last_id(), 2);
+
+    // Cancellation reduces the count, but doesn't affect the ID allocator
+    client.cancel(&first_id, &false);
+    assert_eq!(client.subscription_count(), 2);
+    assert_eq!(client.last_id(), 2);
+}
+
+#[test]
+fn lapsed_subscription_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (first_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let (third_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    client.cancel(&third_id, &false);
+    assert_eq!(client.subscription_count(), 2);
+
+    // Summary outlives the record by the grace period
+    env.as_contract(&client.address, || {
+        let record_ttl = env.storage().persistent().get_ttl(&first_id);
+        let summary_ttl = env.storage().persistent().get_ttl(&("summary", first_id));
+        assert_eq!(summary_ttl, record_ttl + 17280 * 30);
+    });
+
+    // Simulate expired persistent entries
+    env.as_contract(&client.address, || {
+        env.storage().persistent().remove(&first_id);
+        env.storage().persistent().remove(&second_id);
+    });
+
+    // Charge reconciles lapsed records, cancelled and unknown IDs are ignored
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, first_id, third_id, 100]);
+    assert_eq!(client.subscription_count(), 1);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("lapsed")),
+        1
+    );
+
+    let lapsed = client.reap_expired(&config.admin, &vec![&env, first_id, second_id, third_id]);
+    assert_eq!(lapsed, vec![&env, second_id]);
+    assert_eq!(client.subscription_count(), 0);
+    // Aggregates and indexes are reconciled as well
+    assert_eq!(client.active_count(), 0);
+    assert_eq!(client.total_locked(), 0);
+    assert_eq!(
+        client.subscriptions_for_pair(&params.base, &params.quote, &10, &0),
+        vec![&env]
+    );
+    assert_eq!(
+        client.get_owner_subscriptions_full(&owner, &0, &10),
+        vec![&env]
+    );
+    // No tombstones are left behind
+    env.as_contract(&client.address, || {
+        for subscription_id in [first_id, second_id, third_id] {
+            assert_eq!(env.get_subscription_summary(subscription_id), None);
+        }
+    });
+
+    // Already reconciled subscriptions are not counted twice
+    let lapsed = client.reap_expired(&config.admin, &vec![&env, first_id, second_id]);
+    assert_eq!(lapsed.len(), 0);
+    assert_eq!(client.subscription_count(), 0);
+}
+
+#[test]
+fn complexity_factor_test() {
+    let env = Env::default();
+    let asset = |asset: &str, source: &str| TickerAsset {
+        asset: String::from_str(&env, asset),
+        source: String::from_str(&env, source),
+    };
+    let btc = asset("BTC", "source1");
+    let eth = asset("ETH", "source1");
+    let eth_cross = asset("ETH", "source2");
+    let xlm_cross = asset("XLM", "source3");
+
+    // Single source
+    assert_eq!(calc_complexity_factor(&[&btc, &eth]), 1);
+    // Two sources
+    assert_eq!(calc_complexity_factor(&[&btc, &eth_cross]), 2);
+    assert_eq!(calc_complexity_factor(&[&btc, &eth, &eth_cross]), 2);
+    // Three sources
+    assert_eq!(calc_complexity_factor(&[&btc, &eth_cross, &xlm_cross]), 3);
+
+    // Fee scales with the factor
+    assert_eq!(
+        calc_fee(100, &btc, &eth_cross, 120),
+        calc_fee(100, &btc, &eth, 120) * 2
+    );
+}
+
+#[test]
+fn invalid_token_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    let token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let config = |token: &Address, init_token: Option<Address>| ContractConfig {
+        admin: admin.clone(),
+        token: token.clone(),
+        init_token,
+        features: Vec::new(&env),
+        fee: 100,
+    };
+
+    // Token matching the admin address
+    let result = client.try_config(&config(&admin, None));
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidToken))));
+    // All-zero token addresses
+    for zero in [ZERO_ACCOUNT, ZERO_CONTRACT] {
+        let zero = Address::from_string(&String::from_str(&env, zero));
+        let result = client.try_config(&config(&zero, None));
+        assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidToken))));
+        let result = client.try_config(&config(&token, Some(zero)));
+        assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidToken))));
+    }
+    // Init token matching the admin address
+    let result = client.try_config(&config(&token, Some(admin.clone())));
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidToken))));
+
+    client.config(&config(&token, None));
+    assert_eq!(client.token(), token);
+}
+
+#[test]
+fn charge_result_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (first_id, _) = client.create_subscription(&params, &(fee * 4), &None);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let (third_id, _) = client.create_subscription(&params, &(fee * 6), &None);
+
+    set_timestamp(&env, 86400);
+    let result = client.charge(&config.admin, &vec![&env, first_id, second_id, third_id]);
+    assert_eq!(
+        result.charges,
+        vec![&env, (first_id, fee), (second_id, fee), (third_id, fee)]
+    );
+    assert_eq!(result.suspended, vec![&env, second_id]);
+
+    // Already suspended subscriptions are skipped
+    set_timestamp(&env, 86400 * 2);
+    let result = client.charge(&config.admin, &vec![&env, first_id, second_id]);
+    assert_eq!(result.charges, vec![&env, (first_id, fee)]);
+    assert_eq!(result.suspended, vec![&env, first_id]);
+}
+
+#[test]
+fn format_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+
+    // Unknown format is rejected on creation
+    params.format = 3;
+    let result = client.try_create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidFormat))));
+
+    params.format = 1;
+    let (subscription_id, subscription) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(subscription.format, 1);
+
+    // Format can be updated to any supported value
+    for format in 0..=2 {
+        client.set_format(&subscription_id, &format);
+        assert_eq!(client.get_subscription(&subscription_id).format, format);
+    }
+    let result = client.try_set_format(&subscription_id, &u32::MAX);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidFormat))));
+    assert_eq!(client.get_subscription(&subscription_id).format, 2);
+}
+
+#[test]
+fn system_subscription_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    assert_eq!(client.user_id_start(), 1);
+
+    // Start ID must be positive
+    let result = client.try_set_user_id_start(&0);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidIdRange)))
+    );
+    client.set_user_id_start(&100);
+    assert_eq!(client.user_id_start(), 100);
+
+    // System subscriptions can be created only in the reserved range
+    let (system_id, system) = client.admin_create_subscription(&5, &params);
+    assert_eq!(system_id, 5);
+    assert_eq!(system.balance, 0);
+    let result = client.try_admin_create_subscription(&100, &params);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidIdRange)))
+    );
+    let result = client.try_admin_create_subscription(&0, &params);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidIdRange)))
+    );
+    let result = client.try_admin_create_subscription(&5, &params);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::IdCollision))));
+
+    // User subscriptions are allocated from the start ID
+    let (first_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(first_id, 100);
+    assert_eq!(second_id, 101);
+    assert_eq!(client.subscription_count(), 3);
+
+    // Range can't be changed once subscriptions exist
+    let result = client.try_set_user_id_start(&10);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidIdRange)))
+    );
+
+    // System subscriptions are skipped by charge
+    set_timestamp(&env, 86400);
+    let result = client.charge(&config.admin, &vec![&env, system_id, first_id]);
+    assert_eq!(result.charges, vec![&env, (first_id, fee)]);
+    assert_eq!(
+        client.get_subscription(&system_id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn repeated_config_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    // Second initialization fails before requesting any auth
+    env.set_auths(&[]);
+    let result = client.try_config(&config);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::AlreadyInitialized)))
+    );
+    assert_eq!(env.auths().len(), 0);
+}
+
+#[test]
+fn balance_history_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 12), &None);
+
+    // History is disabled by default
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(client.balance_history(&subscription_id).len(), 0);
+
+    let result = client.try_set_balance_history_len(&(MAX_BALANCE_HISTORY + 1));
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+    client.set_balance_history_len(&3);
+    assert_eq!(client.balance_history_len(), 3);
+
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(
+        client.balance_history(&subscription_id),
+        vec![&env, (0, fee * 12)]
+    );
+    for day in 1..=3 {
+        set_timestamp(&env, 86400 * day);
+        client.charge(&config.admin, &vec![&env, subscription_id]);
+    }
+    // The oldest snapshot is evicted past the cap
+    assert_eq!(
+        client.balance_history(&subscription_id),
+        vec![
+            &env,
+            (DAY, fee * 11),
+            (DAY * 2, fee * 10),
+            (DAY * 3, fee * 9)
+        ]
+    );
+
+    // History is removed with the subscription
+    client.cancel(&subscription_id, &false);
+    let result = client.try_balance_history(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+}
+
+#[test]
+fn min_fee_floor_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    assert_eq!(client.min_fee_floor(), 0);
+    assert_eq!(client.get_retention_fee(&subscription_id), fee);
+
+    // Floor below the calculated fee has no effect
+    client.set_min_fee_floor(&(fee - 1));
+    assert_eq!(client.get_retention_fee(&subscription_id), fee);
+
+    // Floor above the calculated fee raises it
+    let floor = fee * 2;
+    client.set_min_fee_floor(&floor);
+    assert_eq!(client.min_fee_floor(), floor);
+    assert_eq!(client.get_retention_fee(&subscription_id), floor);
+    assert_eq!(
+        client.fee_curve(&params.base, &params.quote, &vec![&env, 5, 60]),
+        vec![&env, floor, floor]
+    );
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(
+        client.get_subscription(&subscription_id).balance,
+        fee * 8 - floor
+    );
+}
+
+#[test]
+fn teardown_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Teardown is refused while subscriptions exist
+    let result = client.try_teardown();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::HasActiveSubscriptions)))
+    );
+    assert_eq!(client.admin(), Some(config.admin.clone()));
+
+    // Deferred burns must be flushed first
+    client.set_defer_burns(&true);
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    client.cancel(&subscription_id, &false);
+    let result = client.try_teardown();
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::PendingBurns))));
+    client.flush_burns();
+
+    // Settings configured after the initialization are reset as well
+    let worker = Address::generate(&env);
+    client.set_worker(&Some(worker));
+    client.set_treasury(&Some(Address::generate(&env)), &1000);
+    client.set_deposits_paused(&true);
+    client.set_charges_paused(&true);
+    client.add_source(&params.base.source);
+    client.set_defaults(&60, &ThresholdKind::Relative(20));
+    client.set_free_tier(&vec![
+        &env,
+        (
+            params.base.clone(),
+            params.quote.clone(),
+            params.heartbeat.unwrap(),
+        ),
+    ]);
+    client.set_asset_tiers(&vec![&env, (params.base.clone(), 3)]);
+    client.set_paused(&true);
+
+    client.teardown();
+    assert_eq!(client.admin(), None);
+    let result = client.try_fee();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::NotInitialized)))
+    );
+
+    // Contract can be configured again from scratch
+    client.config(&config);
+    assert_eq!(client.admin(), Some(config.admin.clone()));
+    assert_eq!(client.last_id(), 0);
+    assert_eq!(client.worker(), None);
+    assert_eq!(client.treasury(), None);
+    assert!(!client.paused());
+    assert!(!client.deposits_paused());
+    assert!(!client.charges_paused());
+    assert!(!client.defer_burns());
+    assert_eq!(client.sources().len(), 0);
+    assert_eq!(client.defaults(), None);
+    assert_eq!(client.free_tier().len(), 0);
+    assert_eq!(client.asset_tiers().len(), 0);
+    assert_eq!(client.schema_version(), SCHEMA_VERSION);
+}
+
+#[test]
+fn retention_fees_batch_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let (first_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+    params.heartbeat = Some(60);
+    params.quote.source = params.base.source.clone();
+    let (second_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+
+    let fees = client.get_retention_fees(&vec![&env, second_id, 100, first_id]);
+    assert_eq!(
+        fees,
+        vec![
+            &env,
+            client.get_retention_fee(&second_id),
+            0,
+            client.get_retention_fee(&first_id)
+        ]
+    );
+    assert_ne!(fees.get_unchecked(0), fees.get_unchecked(2));
+
+    let mut ids = Vec::new(&env);
+    for id in 0..=MAX_BATCH_SIZE as u64 {
+        ids.push_back(id);
+    }
+    let result = client.try_get_retention_fees(&ids);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+}
+
+#[test]
+fn cancel_for_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+
+    // Owner address must match the subscription owner
+    let result = client.try_cancel_for(&subscription_id, &config.admin);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::Unauthorized))));
+
+    // Without the owner authorization the call fails
+    env.set_auths(&[]);
+    assert!(client.try_cancel_for(&subscription_id, &owner).is_err());
+
+    env.mock_all_auths();
+    client.cancel_for(&subscription_id, &owner);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            owner.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    client.address.clone(),
+                    Symbol::new(&env, "cancel_for"),
+                    (subscription_id, owner.clone()).into_val(&env),
+                )),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
+    assert_eq!(token.balance(&owner), owner_balance + fee as i128);
+    assert!(client.try_get_subscription(&subscription_id).is_err());
+}
+
+#[test]
+fn webhook_prefix_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let prefix = Bytes::from_array(&env, &[0xec, 0x01]);
+    client.set_webhook_prefix(&Some(prefix.clone()));
+    assert_eq!(client.webhook_prefix(), Some(prefix.clone()));
+
+    // Webhook with the expected prefix
+    let mut webhook = prefix.clone();
+    webhook.extend_from_array(&[7; 32]);
+    params.webhook = webhook;
+    client.create_subscription(&params, &(fee * 3), &None);
+
+    // Plaintext and too short webhooks are rejected
+    params.webhook = Bytes::from_slice(&env, b"https://example.com/hook");
+    let result = client.try_create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::WebhookNotEncrypted)))
+    );
+    params.webhook = Bytes::from_array(&env, &[0xec]);
+    let result = client.try_create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::WebhookNotEncrypted)))
+    );
+
+    // Any webhook is accepted with the check disabled
+    client.set_webhook_prefix(&None);
+    assert_eq!(client.webhook_prefix(), None);
+    params.webhook = Bytes::from_slice(&env, b"https://example.com/hook");
+    client.create_subscription(&params, &(fee * 3), &None);
+}
+
+#[test]
+fn full_config_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let worker = Address::generate(&env);
+    client.set_worker(&Some(worker.clone()));
+    client.set_fee(&200);
+    client.set_min_fee_floor(&300);
+    client.set_protocol_version(&7);
+    client.set_trigger_interval(&60000);
+    client.set_defer_burns(&true);
+    client.set_user_id_start(&10);
+    client.set_balance_history_len(&4);
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    client.add_source(&params.base.source);
+    client.add_source(&params.quote.source);
+    client.set_defaults(&60, &ThresholdKind::Relative(20));
+    client.set_asset_tiers(&vec![&env, (params.base.clone(), 3)]);
+    client.set_free_tier(&vec![&env, (params.base.clone(), params.quote.clone(), 60)]);
+    let (subscription_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    client.set_webhook_prefix(&Some(Bytes::from_array(&env, &[1, 2])));
+
+    let full_config = client.get_full_config();
+    assert_eq!(Some(full_config.admin.clone()), client.admin());
+    assert_eq!(full_config.admin, config.admin);
+    assert_eq!(full_config.worker, client.worker());
+    assert_eq!(full_config.token, client.token());
+    assert_eq!(full_config.init_token, None);
+    assert_eq!(full_config.fee, client.fee());
+    assert_eq!(full_config.min_fee_floor, client.min_fee_floor());
+    assert_eq!(
+        full_config.min_fee_change_interval,
+        client.min_fee_change_interval()
+    );
+    assert_eq!(full_config.protocol_version, client.version());
+    assert_eq!(full_config.trigger_interval, client.trigger_interval());
+    assert_eq!(full_config.defer_burns, client.defer_burns());
+    assert_eq!(full_config.user_id_start, client.user_id_start());
+    assert_eq!(
+        full_config.balance_history_len,
+        client.balance_history_len()
+    );
+    assert_eq!(full_config.webhook_prefix, client.webhook_prefix());
+    assert_eq!(full_config.token_decimals, client.token_decimals());
+    assert_eq!(full_config.pending_burn, client.pending_burn());
+    assert_eq!(full_config.sources, client.sources());
+    assert_eq!(full_config.defaults.first(), client.defaults());
+    assert_eq!(full_config.asset_tiers, client.asset_tiers());
+    assert_eq!(full_config.free_tier, client.free_tier());
+    assert!(full_config.pending_burn > 0);
+    assert_eq!(full_config.sources.len(), 2);
+    assert_eq!(full_config.defaults.len(), 1);
+    assert_eq!(
+        (
+            full_config.fee,
+            full_config.protocol_version,
+            full_config.user_id_start
+        ),
+        (200, 7, 10)
+    );
+}
+
+#[test]
+fn expired_subscription_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Suspended
+    );
+
+    // Subscription is still suspended before the expiration period ends
+    set_timestamp(&env, 86400 * 30);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Suspended
+    );
+
+    set_timestamp(&env, 86400 * 31);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Expired
+    );
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("expired")),
+        1
+    );
+
+    // Deposits into expired subscriptions are rejected
+    let result = client.try_deposit(&owner, &subscription_id, &(fee * 3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::SubscriptionExpired)))
+    );
+
+    // Expired subscription can still be cancelled
+    client.cancel(&subscription_id, &false);
+    assert_eq!(client.subscription_count(), 0);
+}
+
+#[test]
+fn subscriptions_for_pair_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let mut reversed = params.clone();
+    reversed.base = params.quote.clone();
+    reversed.quote = params.base.clone();
+    let amount = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    ) * 3;
+
+    let (first_id, _) = client.create_subscription(&params, &amount, &None);
+    let (reversed_id, _) = client.create_subscription(&reversed, &amount, &None);
+    let (second_id, _) = client.create_subscription(&params, &amount, &None);
+    let (third_id, _) = client.create_subscription(&params, &amount, &None);
+
+    let pair = |limit: u32, start_after_id: u64| {
+        client.subscriptions_for_pair(&params.base, &params.quote, &limit, &start_after_id)
+    };
+    assert_eq!(pair(10, 0), vec![&env, first_id, second_id, third_id]);
+    assert_eq!(
+        client.subscriptions_for_pair(&reversed.base, &reversed.quote, &10, &0),
+        vec![&env, reversed_id]
+    );
+
+    // Pagination continues after the last ID of the previous page
+    assert_eq!(pair(2, 0), vec![&env, first_id, second_id]);
+    assert_eq!(pair(2, second_id), vec![&env, third_id]);
+    assert_eq!(pair(2, third_id).len(), 0);
+
+    // Cancelled subscriptions are removed from the index
+    client.cancel(&second_id, &false);
+    assert_eq!(pair(10, 0), vec![&env, first_id, third_id]);
+    assert_eq!(pair(10, first_id), vec![&env, third_id]);
+    client.cancel(&first_id, &false);
+    client.cancel(&third_id, &false);
+    assert_eq!(pair(10, 0).len(), 0);
+
+    // Each ID is stored in a separate entry, the index is rebuilt from scratch after being emptied
+    let (fourth_id, _) = client.create_subscription(&params, &amount, &None);
+    assert_eq!(pair(10, 0), vec![&env, fourth_id]);
+}
+
+#[test]
+fn idempotency_key_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let token = TokenClient::new(&env, &config.token);
+    let idempotency_key = Some(BytesN::from_array(&env, &[1; 32]));
+
+    let (subscription_id, subscription) =
+        client.create_subscription(&params, &(fee * 3), &idempotency_key);
+    let owner_balance = token.balance(&owner);
+
+    // Retry with the same key returns the existing subscription without charging again
+    let retried = client.create_subscription(&params, &(fee * 3), &idempotency_key);
+    assert_eq!(retried, (subscription_id, subscription));
+    assert_eq!(token.balance(&owner), owner_balance);
+    assert_eq!(client.subscription_count(), 1);
+
+    // Same key of another owner is independent
+    let other_owner = generate_funded_owner(&env, &config);
+    let mut other_params = params.clone();
+    other_params.owner = other_owner;
+    let (other_id, _) = client.create_subscription(&other_params, &(fee * 3), &idempotency_key);
+    assert_ne!(other_id, subscription_id);
+
+    // New key creates a new subscription
+    let idempotency_key = Some(BytesN::from_array(&env, &[2; 32]));
+    let (new_id, _) = client.create_subscription(&params, &(fee * 3), &idempotency_key);
+    assert_ne!(new_id, subscription_id);
+    assert_eq!(client.subscription_count(), 3);
+
+    // Keys are stored as is
+    env.as_contract(&client.address, || {
+        assert_eq!(
+            env.get_idempotency_key(&owner, &BytesN::from_array(&env, &[2; 32])),
+            Some(new_id)
+        );
+    });
+}
+
+#[test]
+fn adjust_balance_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let token = TokenClient::new(&env, &config.token);
+    let contract_balance = token.balance(&client.address);
+
+    // Credit
+    client.adjust_balance(&subscription_id, &(fee as i128), &symbol_short!("dispute"));
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 2);
+    // Debit
+    client.adjust_balance(&subscription_id, &-(fee as i128 / 2), &symbol_short!("bug"));
+    assert_eq!(
+        client.get_subscription(&subscription_id).balance,
+        fee * 2 - fee / 2
+    );
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("adjusted")),
+        2
+    );
+
+    // Balance can't go negative
+    let result =
+        client.try_adjust_balance(&subscription_id, &-(fee as i128 * 2), &symbol_short!("bug"));
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+    // Balance can't overflow
+    let result =
+        client.try_adjust_balance(&subscription_id, &(u64::MAX as i128), &symbol_short!("bug"));
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+
+    // Tokens are not moved
+    assert_eq!(token.balance(&client.address), contract_balance);
+
+    // Balance can't drop below the reserve
+    let mut reserved_params = params.clone();
+    reserved_params.reserve = fee;
+    let (reserved_id, _) = client.create_subscription(&reserved_params, &(fee * 4), &None);
+    let result =
+        client.try_adjust_balance(&reserved_id, &-(fee as i128 + 1), &symbol_short!("bug"));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidReserve)))
+    );
+    client.adjust_balance(&reserved_id, &-(fee as i128), &symbol_short!("bug"));
+    assert_eq!(client.get_subscription(&reserved_id).balance, fee);
+}
+
+#[test]
+fn export_subscription_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    client.set_format(&subscription_id, &2);
+
+    let data = client.export_subscription(&subscription_id);
+    assert_eq!(
+        client.decode_subscription(&data),
+        client.get_subscription(&subscription_id)
+    );
+
+    // Data with another layout version is rejected
+    let subscription = client.get_subscription(&subscription_id);
+    let data = (SUBSCRIPTION_LAYOUT_VERSION + 1, subscription).to_xdr(&env);
+    let result = client.try_decode_subscription(&data);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidSubscriptionData)))
+    );
+    // Malformed data is rejected by the host while decoding
+    let result = client.try_decode_subscription(&Bytes::from_array(&env, &[1, 2, 3]));
+    assert!(result.is_err());
+}
+
+#[test]
+fn charge_skips_suspended_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (active_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let (suspended_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, active_id, suspended_id]);
+    let suspended = client.get_subscription(&suspended_id);
+    assert_eq!(suspended.status, SubscriptionStatus::Suspended);
+    assert_eq!(suspended.balance, fee / 2);
+    let charged_events = count_events(&env, &client.address, symbol_short!("charged"));
+
+    // Suspended subscription keeps its residual balance and produces no events
+    set_timestamp(&env, 86400 * 3);
+    let result = client.charge(&config.admin, &vec![&env, active_id, suspended_id]);
+    assert_eq!(result.charges, vec![&env, (active_id, fee * 2)]);
+    assert_eq!(client.get_subscription(&suspended_id), suspended);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("charged")),
+        charged_events + 1
+    );
+}
+
+#[test]
+fn fee_change_interval_test() {
+    let (env, client, _) = init_contract_with_admin();
+
+    // Without the interval the fee can be changed at any time
+    client.set_fee(&200);
+    client.set_fee(&300);
+
+    client.set_min_fee_change_interval(&(DAY * 7));
+    assert_eq!(client.min_fee_change_interval(), DAY * 7);
+
+    set_timestamp(&env, 86400 * 6);
+    let result = client.try_set_fee(&400);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::FeeChangeTooSoon)))
+    );
+    assert_eq!(client.fee(), 300);
+
+    set_timestamp(&env, 86400 * 7);
+    client.set_fee(&400);
+    assert_eq!(client.fee(), 400);
+
+    // Interval is counted from the last successful change
+    set_timestamp(&env, 86400 * 8);
+    let result = client.try_set_fee(&500);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::FeeChangeTooSoon)))
+    );
+}
+
+#[test]
+fn expires_at_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let token = TokenClient::new(&env, &config.token);
+
+    // Expiry date must be in the future
+    set_timestamp(&env, 1000);
+    params.expires_at = Some(1000 * 1000);
+    let result = client.try_create_subscription(&params, &(fee * 10), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimestamp)))
+    );
+
+    // Trial expiring in 2.5 days
+    params.expires_at = Some(1000 * 1000 + DAY * 5 / 2);
+    params.reserve = fee;
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Subscription is charged as usual before the expiry date
+    set_timestamp(&env, 1000 + 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 6);
+
+    // Fees are not accrued past the expiry date, the rest is refunded including the reserve
+    let owner_balance = token.balance(&owner);
+    set_timestamp(&env, 1000 + 86400 * 5);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.charges, vec![&env, (subscription_id, 0)]);
+    assert_eq!(token.balance(&owner), owner_balance + (fee * 6) as i128);
+    // Expired subscription is removed right away
+    assert_eq!(
+        client.try_get_subscription(&subscription_id).err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+    assert_eq!(client.subscription_count(), 0);
+    assert_eq!(client.active_count(), 0);
+    assert_eq!(client.total_locked(), 0);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("expired")),
+        1
+    );
+}
+
+#[test]
+fn ttl_clamp_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Deposit covering more days than the max TTL allows
+    let amount = fee * 1000;
+    StellarAssetClient::new(&env, &config.token).mint(&owner, &(amount as i128));
+    client.deposit(&owner, &subscription_id, &amount, &None);
+    assert_eq!(
+        client.get_subscription(&subscription_id).balance,
+        fee + amount
+    );
+
+    let max_ttl = env.as_contract(&client.address, || env.storage().max_ttl());
+    let ttl = env.as_contract(&client.address, || {
+        env.storage().persistent().get_ttl(&subscription_id)
+    });
+    assert_eq!(ttl, max_ttl);
+}
+
+#[test]
+fn ownership_transfer_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let new_owner = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Nothing to accept without a proposal
+    let result = client.try_accept_transfer(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::NoPendingTransfer)))
+    );
+
+    // Propose -> cancel
+    client.propose_transfer(&subscription_id, &new_owner);
+    assert_eq!(
+        client.get_subscription(&subscription_id).pending_owner,
+        Some(new_owner.clone())
+    );
+    client.cancel_transfer(&subscription_id);
+    assert_eq!(
+        client.get_subscription(&subscription_id).pending_owner,
+        None
+    );
+    let result = client.try_accept_transfer(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::NoPendingTransfer)))
+    );
+
+    // Acceptance requires the proposed owner authorization
+    client.propose_transfer(&subscription_id, &new_owner);
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &stranger,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "accept_transfer",
+                args: (subscription_id,).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_accept_transfer(&subscription_id);
+    assert!(result.is_err());
+    assert_eq!(client.get_subscription(&subscription_id).owner, owner);
+
+    // Propose -> accept
+    client
+        .mock_auths(&[MockAuth {
+            address: &new_owner,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "accept_transfer",
+                args: (subscription_id,).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .accept_transfer(&subscription_id);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.owner, new_owner);
+    assert_eq!(subscription.pending_owner, None);
+    assert!(client.is_owner(&subscription_id, &new_owner));
+}
+
+#[test]
+fn charge_stats_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (first_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let (third_id, _) = client.create_subscription(&params, &(fee * 5), &None);
+    assert_eq!(client.active_count(), 3);
+    assert_eq!(client.total_locked(), fee * 12);
+
+    set_timestamp(&env, 86400);
+    // Unknown IDs count as processed but produce no charge
+    client.charge(
+        &config.admin,
+        &vec![&env, first_id, second_id, third_id, third_id + 100],
+    );
+
+    // Only the last stats event is checked
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(address, topics, _)| {
+            address == &client.address
+                && Symbol::try_from_val(&env, &topics.get_unchecked(2))
+                    == Ok(symbol_short!("stats"))
+        })
+        .last()
+        .unwrap();
+    let stats = <(u32, u64, u32, u64, u64)>::try_from_val(&env, &data).unwrap();
+    assert_eq!(stats, (4, fee * 3, 1, 2, fee * 9));
+    assert_eq!(client.active_count(), 2);
+    assert_eq!(client.total_locked(), fee * 9);
+
+    // Aggregates follow deposits, revivals and cancellations
+    client.deposit(&owner, &second_id, &(fee * 2), &None);
+    assert_eq!(client.active_count(), 3);
+    assert_eq!(client.total_locked(), fee * 10);
+    client.cancel(&first_id, &false);
+    assert_eq!(client.active_count(), 2);
+    assert_eq!(client.total_locked(), fee * 3);
+}
+
+#[test]
+fn max_acceptable_fee_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (capped_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let (uncapped_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let (cancelled_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    client.set_max_acceptable_fee(&capped_id, &Some(fee));
+    client.set_max_acceptable_fee(&cancelled_id, &Some(fee));
+    client.set_auto_cancel(&cancelled_id, &true);
+    client.set_balance_history_len(&5);
+
+    // Fee within the cap is charged as usual
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, capped_id, uncapped_id]);
+    assert_eq!(client.get_subscription(&capped_id).balance, fee * 7);
+
+    // Fee increase trips the cap, elapsed days are settled at the accepted fee
+    client.set_fee(&(config.fee * 2));
+    set_timestamp(&env, 86400 * 3);
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+    let charged_events = count_events(&env, &client.address, symbol_short!("charged"));
+    let result = client.charge(
+        &config.admin,
+        &vec![&env, capped_id, uncapped_id, cancelled_id],
+    );
+    // Settlements are reported like regular charges
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("charged")),
+        charged_events + 3
+    );
+    assert_eq!(result.suspended, vec![&env, capped_id]);
+    assert_eq!(
+        client.balance_history(&capped_id).last(),
+        Some((86400 * 3 * 1000, fee * 5))
+    );
+    let capped = client.get_subscription(&capped_id);
+    assert_eq!(capped.status, SubscriptionStatus::Suspended);
+    assert_eq!(capped.balance, fee * 5);
+    // Auto-cancelled subscription is refunded after the settlement
+    assert_eq!(token.balance(&owner), owner_balance + (fee * 5) as i128);
+    assert_eq!(
+        count_events(&env, &client.address, Symbol::new(&env, "fee_exceeded")),
+        1
+    );
+    // Subscription without the cap is charged at the new rate
+    assert_eq!(
+        client.get_subscription(&uncapped_id).balance,
+        fee * 7 - client.get_retention_fee(&uncapped_id) * 2
+    );
+}
+
+#[test]
+fn features_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    let token = env
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    client.config(&ContractConfig {
+        admin: admin.clone(),
+        token,
+        init_token: None,
+        features: vec![&env, symbol_short!("multi_tok")],
+        fee: 100,
+    });
+
+    assert!(client.supports(&symbol_short!("multi_tok")));
+    assert!(!client.supports(&symbol_short!("merkle")));
+
+    // Toggle features
+    client.set_features(&vec![&env, symbol_short!("merkle")]);
+    assert!(client.supports(&symbol_short!("merkle")));
+    assert!(!client.supports(&symbol_short!("multi_tok")));
+    assert_eq!(
+        client.get_full_config().features,
+        vec![&env, symbol_short!("merkle")]
+    );
+    client.set_features(&Vec::new(&env));
+    assert!(!client.supports(&symbol_short!("merkle")));
+}
+
+#[test]
+fn retire_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Retirement requires the contract to be paused
+    client.cancel(&subscription_id, &false);
+    let result = client.try_retire();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::ContractNotPaused)))
+    );
+
+    // New subscriptions are blocked while paused
+    client.set_paused(&true);
+    assert!(client.paused());
+    let result = client.try_create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::ContractPaused)))
+    );
+
+    client.retire();
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("retired")),
+        1
+    );
+    assert_eq!(client.admin(), None);
+    assert_eq!(client.worker(), None);
+    assert!(!client.paused());
+    assert_eq!(
+        env.as_contract(&client.address, || env.get_subscription_count()),
+        0
+    );
+    let result = client.try_fee();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::NotInitialized)))
+    );
+
+    // Retired contract can't be claimed by anyone
+    let mut claim = config.clone();
+    claim.admin = Address::generate(&env);
+    let result = client.try_config(&claim);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::ContractRetired)))
+    );
+    assert_eq!(client.admin(), None);
+}
+
+#[test]
+fn retire_with_subscriptions_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    client.create_subscription(&params, &(fee * 3), &None);
+
+    client.set_paused(&true);
+    let result = client.try_retire();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::HasActiveSubscriptions)))
+    );
+    assert_eq!(client.admin(), Some(config.admin));
+}
+
+#[test]
+fn retire_with_pending_burns_test() {
+    let (env, client, config) = init_contract_with_admin();
+    client.set_defer_burns(&true);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    client.cancel(&subscription_id, &false);
+    assert_eq!(client.pending_burn(), fee);
+
+    // Deferred burns must be flushed before the retirement
+    client.set_paused(&true);
+    let result = client.try_retire();
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::PendingBurns))));
+    client.flush_burns();
+    client.retire();
+    let token = TokenClient::new(&env, &config.token);
+    assert_eq!(token.balance(&client.address), 0);
+}
+
+#[test]
+fn deposit_to_runway_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 2);
+
+    // Top up the shortfall
+    assert_eq!(
+        client.deposit_to_runway(&owner, &subscription_id, &5),
+        fee * 3
+    );
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 5);
+
+    // Already funded subscription is not topped up
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+    assert_eq!(client.deposit_to_runway(&owner, &subscription_id, &3), 0);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 5);
+    assert_eq!(token.balance(&owner), owner_balance);
+
+    // Reserved balance doesn't count towards the runway
+    client.set_reserve(&subscription_id, &(fee * 2));
+    assert_eq!(client.deposit_to_runway(&owner, &subscription_id, &4), fee);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 6);
+
+    // Small shortfalls are rounded up to the minimum deposit
+    client.set_min_deposit(&(fee * 2));
+    assert_eq!(
+        client.deposit_to_runway(&owner, &subscription_id, &5),
+        fee * 2
+    );
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 8);
+}
+
+#[test]
+fn deposit_to_runway_revival_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Suspended);
+    assert_eq!(subscription.balance, fee / 2);
+
+    // Revival fee is included in the target
+    assert_eq!(
+        client.deposit_to_runway(&owner, &subscription_id, &4),
+        fee * 5 - fee / 2
+    );
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+    assert_eq!(subscription.balance, fee * 4);
+}
+
+#[test]
+fn would_trigger_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (relative_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    params.threshold = ThresholdParam::Custom(ThresholdKind::Absolute(10));
+    let (absolute_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    assert!(!client.would_trigger(&relative_id, &9));
+    assert!(client.would_trigger(&relative_id, &10));
+    assert!(client.would_trigger(&relative_id, &11));
+    // Absolute thresholds can't be compared with a relative move
+    assert!(!client.would_trigger(&absolute_id, &11));
+    assert!(!client.would_trigger(&100, &11));
+}
+
+#[test]
+fn treasury_share_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let treasury = Address::generate(&env);
+    client.set_treasury(&Some(treasury.clone()), &3333);
+    assert_eq!(client.treasury(), Some((treasury.clone(), 3333)));
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    ) + 1;
+    let token = TokenClient::new(&env, &config.token);
+    let amount = fee * 5;
+    let (subscription_id, _) = client.create_subscription(&params, &amount, &None);
+    let init_fee = amount - client.get_subscription(&subscription_id).balance;
+
+    // Treasury share is rounded down, the remainder is burned
+    let treasury_share = init_fee * 3333 / 10000;
+    assert_eq!(token.balance(&treasury), treasury_share as i128);
+    let burned = amount as i128 - token.balance(&client.address) - token.balance(&treasury);
+    assert_eq!(burned, (init_fee - treasury_share) as i128);
+
+    // Charged fees are split the same way
+    set_timestamp(&env, 86400);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    let (_, charged) = result.charges.get_unchecked(0);
+    let total_fees = init_fee + charged;
+    let treasury_share = treasury_share + charged * 3333 / 10000;
+    assert_eq!(token.balance(&treasury), treasury_share as i128);
+    let burned = amount as i128 - token.balance(&client.address) - token.balance(&treasury);
+    assert_eq!(burned + treasury_share as i128, total_fees as i128);
+    // Burn statistics exclude the treasury share
+    assert_eq!(client.fees_burned_between(&0, &1), burned as u64);
+
+    // Share above 100% is rejected
+    let result = client.try_set_treasury(&Some(treasury), &10001);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+    client.set_treasury(&None, &0);
+    assert_eq!(client.treasury(), None);
+}
+
+#[test]
+fn cancel_burn_residual_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let token = TokenClient::new(&env, &config.token);
+    let treasury = Address::generate(&env);
+    client.set_treasury(&Some(treasury.clone()), &3333);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (refunded_id, _) = client.create_subscription(&params, &(fee * 5), &None);
+    let (burned_id, _) = client.create_subscription(&params, &(fee * 5), &None);
+
+    // Refund path returns the balance to the owner
+    let owner_balance = token.balance(&owner);
+    client.cancel(&refunded_id, &false);
+    assert_eq!(token.balance(&owner), owner_balance + (fee * 3) as i128);
+    let (_, _, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        <(u64, u64, bool, u32)>::try_from_val(&env, &data).unwrap(),
+        (refunded_id, 0, false, 7)
+    );
+
+    // Burn path leaves the owner balance untouched and reports the burned amount
+    let owner_balance = token.balance(&owner);
+    let treasury_balance = token.balance(&treasury);
+    client.cancel(&burned_id, &true);
+    assert_eq!(token.balance(&owner), owner_balance);
+    assert_eq!(token.balance(&client.address), 0);
+    // The residual is burned entirely, the treasury share doesn't apply
+    assert_eq!(token.balance(&treasury), treasury_balance);
+    let (_, _, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        <(u64, u64, bool, u32)>::try_from_val(&env, &data).unwrap(),
+        (burned_id, fee * 3, false, 7)
+    );
+}
+
+#[test]
+fn deposit_auth_bound_to_amount_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Authorization for one amount can't be used for another
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &owner,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "deposit",
+                args: (owner.clone(), subscription_id, fee, None::<u64>).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_deposit(&owner, &subscription_id, &(fee * 2), &None);
+    assert!(result.is_err());
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee);
+
+    // Matching authorization is accepted
+    let transfer_args = (owner.clone(), client.address.clone(), fee as i128).into_val(&env);
+    client
+        .mock_auths(&[MockAuth {
+            address: &owner,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "deposit",
+                args: (owner.clone(), subscription_id, fee, None::<u64>).into_val(&env),
+                sub_invokes: &[MockAuthInvoke {
+                    contract: &config.token,
+                    fn_name: "transfer",
+                    args: transfer_args,
+                    sub_invokes: &[],
+                }],
+            },
+        }])
+        .deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 2);
+}
+
+#[test]
+fn create_subscription_auth_bound_to_amount_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+
+    let result = client
+        .mock_auths(&[MockAuth {
+            address: &owner,
+            invoke: &MockAuthInvoke {
+                contract: &client.address,
+                fn_name: "create_subscription",
+                args: (params.clone(), fee * 3).into_val(&env),
+                sub_invokes: &[],
+            },
+        }])
+        .try_create_subscription(&params, &(fee * 5), &None);
+    assert!(result.is_err());
+    assert_eq!(client.subscription_count(), 0);
+}
+
+#[test]
+fn next_charge_at_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(client.next_charge_at(&subscription_id), DAY);
+
+    // Next charge time advances by exactly one period after a charge
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(client.next_charge_at(&subscription_id), DAY * 2);
+
+    // Suspended subscriptions are not due
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Suspended
+    );
+    assert_eq!(client.next_charge_at(&subscription_id), 0);
+}
+
+#[test]
+fn check_invariants_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, subscription) = client.create_subscription(&params, &(fee * 3), &None);
+    assert!(client.check_invariants(&subscription_id));
+    assert!(!client.check_invariants(&100));
+
+    // Suspended subscriptions are consistent as well
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert!(client.check_invariants(&subscription_id));
+
+    let inject = |subscription: &Subscription| {
+        env.as_contract(&client.address, || {
+            env.set_subscription(subscription_id, subscription)
+        });
+    };
+
+    // Active subscription with a suspension timestamp
+    let mut corrupted = subscription.clone();
+    corrupted.suspended_at = Some(1);
+    inject(&corrupted);
+    assert!(!client.check_invariants(&subscription_id));
+
+    // Updated in the future
+    let mut corrupted = subscription.clone();
+    corrupted.updated = now(&env) + 1;
+    inject(&corrupted);
+    assert!(!client.check_invariants(&subscription_id));
+
+    // Out of range threshold and heartbeat
+    let mut corrupted = subscription.clone();
+    corrupted.threshold = ThresholdKind::Relative(0);
+    inject(&corrupted);
+    assert!(!client.check_invariants(&subscription_id));
+    let mut corrupted = subscription.clone();
+    corrupted.heartbeat = 1;
+    inject(&corrupted);
+    assert!(!client.check_invariants(&subscription_id));
+
+    inject(&subscription);
+    assert!(client.check_invariants(&subscription_id));
+}
+
+#[test]
+fn notification_window_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    params.active_from_minute = Some(810);
+    params.active_to_minute = Some(1200);
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.active_from_minute, Some(810));
+    assert_eq!(subscription.active_to_minute, Some(1200));
+
+    // Window may wrap around midnight
+    params.active_from_minute = Some(1439);
+    params.active_to_minute = Some(0);
+    client.create_subscription(&params, &(fee * 3), &None);
+
+    // Out of range bounds are rejected
+    params.active_to_minute = Some(1440);
+    let result = client.try_create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimeWindow)))
+    );
+
+    // Both bounds must be set
+    params.active_to_minute = None;
+    let result = client.try_create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimeWindow)))
+    );
+}
+
+#[test]
+fn config_hash_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(client.config_hash(&subscription_id), None);
+
+    let hash = env
+        .crypto()
+        .sha256(&Bytes::from_slice(&env, b"{\"headers\":{}}"));
+    let hash: BytesN<32> = hash.into();
+    client.set_config_hash(&subscription_id, &Some(hash.clone()));
+    assert_eq!(client.config_hash(&subscription_id), Some(hash.clone()));
+
+    // Commitment follows the subscription when its ID is remapped
+    client.remap_id(&subscription_id, &100);
+    assert_eq!(client.config_hash(&100), Some(hash));
+    env.as_contract(&client.address, || {
+        assert_eq!(env.get_config_hash(subscription_id), None);
+    });
+
+    // Commitment can be updated and removed
+    let updated_hash = BytesN::from_array(&env, &[7; 32]);
+    client.set_config_hash(&100, &Some(updated_hash.clone()));
+    assert_eq!(client.config_hash(&100), Some(updated_hash));
+    client.set_config_hash(&100, &None);
+    assert_eq!(client.config_hash(&100), None);
+
+    // Commitment is removed together with the subscription
+    client.set_config_hash(&100, &Some(BytesN::from_array(&env, &[3; 32])));
+    client.cancel(&100, &false);
+    env.as_contract(&client.address, || {
+        assert_eq!(env.get_config_hash(100), None);
+    });
+}
+
+#[test]
+fn update_contract_event_test() {
+    let (env, client, _) = init_contract_with_admin();
+    client.set_protocol_version(&7);
+
+    // Minimal module carrying only the protocol 21 environment interface version
+    let mut wasm = std::vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x00, 30, 17];
+    wasm.extend_from_slice(b"contractenvmetav0");
+    wasm.extend_from_slice(&[0, 0, 0, 0]);
+    wasm.extend_from_slice(&(21u64 << 32).to_be_bytes());
+    let wasm_hash = env
+        .deployer()
+        .upload_contract_wasm(Bytes::from_slice(&env, &wasm));
+
+    client.update_contract(&wasm_hash, &Vec::new(&env), &8);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        Symbol::try_from_val(&env, &topics.get_unchecked(3)),
+        Ok(symbol_short!("wasm"))
+    );
+    assert_eq!(
+        <(BytesN<32>, u32)>::try_from_val(&env, &data).unwrap(),
+        (wasm_hash, 8)
+    );
+    // The new version is stored, not the one of the replaced code
+    env.as_contract(&client.address, || {
+        assert_eq!(env.get_protocol_version(), Some(8));
+    });
+}
+
+#[test]
+fn admin_functions_require_initialization_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let caller = Address::generate(&env);
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    let not_initialized = Some(Ok(contract_error(Error::NotInitialized)));
+
+    assert_eq!(
+        client.try_charge(&caller, &vec![&env, 1]).err(),
+        not_initialized
+    );
+    assert_eq!(
+        client
+            .try_trigger(&caller, &1, &BytesN::from_array(&env, &[0; 32]))
+            .err(),
+        not_initialized
+    );
+    assert_eq!(client.try_set_fee(&100).err(), not_initialized);
+    assert_eq!(
+        client
+            .try_update_contract(&BytesN::from_array(&env, &[0; 32]), &Vec::new(&env), &1)
+            .err(),
+        not_initialized
+    );
+}
+
+#[test]
+fn owner_subscriptions_full_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let mut ids = Vec::<u64>::new(&env);
+    for _ in 0..MAX_BATCH_SIZE + 2 {
+        let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+        ids.push_back(subscription_id);
+    }
+    let other_owner = generate_funded_owner(&env, &config);
+    let other_params = generate_subscription_params(&env, &other_owner);
+    let (other_id, _) = client.create_subscription(&other_params, &(fee * 3), &None);
+
+    // Pagination across more subscriptions than the limit
+    let page = client.get_owner_subscriptions_full(&owner, &0, &100);
+    assert_eq!(page.len(), MAX_BATCH_SIZE);
+    let (first_id, first) = page.get_unchecked(0);
+    assert_eq!(first_id, ids.get_unchecked(0));
+    assert_eq!(first, client.get_subscription(&first_id));
+    let page = client.get_owner_subscriptions_full(&owner, &first_id, &100);
+    assert_eq!(page.len(), MAX_BATCH_SIZE);
+    let last_page_start = ids.get_unchecked(MAX_BATCH_SIZE - 1);
+    let page = client.get_owner_subscriptions_full(&owner, &last_page_start, &100);
+    assert_eq!(page.len(), 2);
+    assert_eq!(
+        page.get_unchecked(1).0,
+        ids.get_unchecked(MAX_BATCH_SIZE + 1)
+    );
+    assert_eq!(
+        client
+            .get_owner_subscriptions_full(&owner, &ids.get_unchecked(MAX_BATCH_SIZE + 1), &10)
+            .len(),
+        0
+    );
+
+    // Index follows cancellations and ownership transfers
+    client.cancel(&ids.get_unchecked(0), &false);
+    client.propose_transfer(&other_id, &owner);
+    client.accept_transfer(&other_id);
+    let page = client.get_owner_subscriptions_full(&owner, &last_page_start, &100);
+    assert_eq!(page.len(), 3);
+    assert_eq!(page.get_unchecked(page.len() - 1).0, other_id);
+    assert_eq!(
+        client
+            .get_owner_subscriptions_full(&other_owner, &0, &10)
+            .len(),
+        0
+    );
+
+    // Records are not exposed without the owner authorization
+    env.set_auths(&[]);
+    let result = client.try_get_owner_subscriptions_full(&owner, &0, &10);
+    assert!(result.is_err());
+}
+
+#[test]
+fn free_tier_test() {
+    let (env, client, config) = init_contract_with_admin();
+    let token = TokenClient::new(&env, &config.token);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    client.set_free_tier(&vec![
+        &env,
+        (
+            params.base.clone(),
+            params.quote.clone(),
+            params.heartbeat.unwrap(),
+        ),
+    ]);
+    assert_eq!(client.free_tier().len(), 1);
+
+    // No creation fee is burned
+    let owner_balance = token.balance(&owner);
+    let (subscription_id, subscription) = client.create_subscription(&params, &fee, &None);
+    assert!(subscription.free_tier);
+    assert_eq!(subscription.balance, fee);
+    assert_eq!(token.balance(&owner), owner_balance - fee as i128);
+
+    // Free subscription is neither charged nor suspended over time
+    for day in 1..=5 {
+        set_timestamp(&env, 86400 * day);
+        let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+        assert_eq!(result.charges.len(), 0);
+        assert_eq!(result.suspended.len(), 0);
+    }
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+    assert_eq!(subscription.balance, fee);
+    // Free subscriptions are kept for the maximum TTL regardless of the balance
+    let (retention_fee, _, revival_fee, ttl_ledgers) =
+        client.subscription_economics(&subscription_id);
+    assert_eq!(ttl_ledgers, env.storage().max_ttl());
+    assert_eq!((retention_fee, revival_fee), (0, 0));
+    assert_eq!(client.get_retention_fee(&subscription_id), 0);
+    assert_eq!(
+        client.get_retention_fees(&vec![&env, subscription_id]),
+        vec![&env, 0]
+    );
+    // Free subscriptions never run out of funds
+    assert_eq!(
+        client.subscriptions_at_risk(&100, &0, &10),
+        (Vec::new(&env), 0)
+    );
+    // Zero fee limit is accepted on deposits
+    client.deposit(&owner, &subscription_id, &fee, &Some(0));
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 2);
+
+    // Number of free subscriptions per owner is limited
+    let result = client.try_create_subscription(&params, &fee, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::FreeTierLimitReached)))
+    );
+
+    // Other heartbeats are charged as usual
+    let mut paid_params = params.clone();
+    paid_params.heartbeat = Some(10);
+    let paid_fee = calc_fee(config.fee, &params.base, &params.quote, 10);
+    let (_, paid) = client.create_subscription(&paid_params, &(paid_fee * 3), &None);
+    assert!(!paid.free_tier);
+    assert_eq!(paid.balance, paid_fee);
+
+    // Cancellation frees the slot
+    client.cancel(&subscription_id, &false);
+    client.create_subscription(&params, &fee, &None);
+}
+
+#[test]
+fn remap_id_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let idempotency_key = Some(BytesN::from_array(&env, &[1; 32]));
+    let (old_id, subscription) = client.create_subscription(&params, &(fee * 3), &idempotency_key);
+    let (other_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+
+    // Existing IDs can't be overwritten
+    let result = client.try_remap_id(&old_id, &other_id);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::IdCollision))));
+
+    let new_id = client.derive_subscription_id(&owner, &params.base, &params.quote, &1);
+    client.remap_id(&old_id, &new_id);
+    assert_eq!(client.get_subscription(&new_id), subscription);
+    assert_eq!(
+        client.try_get_subscription(&old_id).err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("remapped")),
+        1
+    );
+    assert_eq!(client.subscription_count(), 2);
+
+    // Indexes point to the new ID
+    assert_eq!(
+        client.subscriptions_for_pair(&params.base, &params.quote, &10, &0),
+        vec![&env, other_id, new_id]
+    );
+    let owned = client.get_owner_subscriptions_full(&owner, &0, &10);
+    assert_eq!(owned.get_unchecked(1).0, new_id);
+
+    // Old ID is not reconciled as lapsed
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, old_id]);
+    assert_eq!(client.subscription_count(), 2);
+
+    // Retried creation resolves to the remapped subscription
+    let (retried_id, _) = client.create_subscription(&params, &(fee * 3), &idempotency_key);
+    assert_eq!(retried_id, new_id);
+    assert_eq!(client.subscription_count(), 2);
+
+    // Sequential creation skips records remapped ahead of the counter
+    let ahead_id = client.last_id() + 1;
+    client.remap_id(&new_id, &ahead_id);
+    assert_eq!(client.next_id(), ahead_id + 1);
+    let (created_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(created_id, ahead_id + 1);
+    assert_eq!(client.get_subscription(&ahead_id).owner, owner);
+    let (created_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(created_id, ahead_id + 2);
+    assert_eq!(client.subscription_count(), 4);
+}
+
+#[test]
+fn remap_id_reserved_range_test() {
+    let (env, client, config) = init_contract_with_admin();
+    client.set_user_id_start(&100);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(subscription_id, 100);
+
+    // User subscriptions can't be moved into the system ID range
+    let result = client.try_remap_id(&subscription_id, &99);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidIdRange)))
+    );
+    client.remap_id(&subscription_id, &200);
+    assert_eq!(client.get_subscription(&200).owner, owner);
+}
+
+#[test]
+fn suspend_by_source_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (affected_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let mut other_params = params.clone();
+    other_params.base.source = String::from_str(&env, "source3");
+    other_params.quote.source = String::from_str(&env, "source3");
+    let other_fee = calc_fee(
+        config.fee,
+        &other_params.base,
+        &other_params.quote,
+        other_params.heartbeat.unwrap(),
+    );
+    let (other_id, _) = client.create_subscription(&other_params, &(other_fee * 10), &None);
+    assert_eq!(client.active_count(), 2);
+
+    set_timestamp(&env, 3600);
+    let cursor = client.suspend_by_source(&String::from_str(&env, "source2"), &true, &0, &10);
+    assert_eq!(cursor, 0);
+    let affected = client.get_subscription(&affected_id);
+    assert_eq!(affected.status, SubscriptionStatus::SourceOutage);
+    assert_eq!(affected.outage_at, Some(3600 * 1000));
+    assert_eq!(affected.suspended_at, None);
+    assert_eq!(
+        client.get_subscription(&other_id).status,
+        SubscriptionStatus::Active
+    );
+    assert_eq!(client.active_count(), 1);
+
+    // Nothing is billed during the outage
+    set_timestamp(&env, 86400 * 3);
+    let result = client.charge(&config.admin, &vec![&env, affected_id, other_id]);
+    assert_eq!(result.charges, vec![&env, (other_id, other_fee * 3)]);
+    assert_eq!(client.get_subscription(&affected_id).balance, fee * 8);
+
+    // Deposits don't lift the outage pause nor burn the revival fee
+    client.deposit(&owner, &affected_id, &fee, &None);
+    let affected = client.get_subscription(&affected_id);
+    assert_eq!(affected.status, SubscriptionStatus::SourceOutage);
+    assert_eq!(affected.balance, fee * 9);
+    assert_eq!(client.active_count(), 1);
+
+    let cursor = client.resume_by_source(&String::from_str(&env, "source2"), &0, &10);
+    assert_eq!(cursor, 0);
+    let affected = client.get_subscription(&affected_id);
+    assert_eq!(affected.status, SubscriptionStatus::Active);
+    assert_eq!(affected.outage_at, None);
+    // Billing is shifted by the outage duration, the hour accrued before it is kept
+    assert_eq!(affected.last_charged, (86400 * 3 - 3600) * 1000);
+    assert_eq!(client.active_count(), 2);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("resumed")),
+        1
+    );
+
+    // Billing resumes where it stopped before the outage
+    set_timestamp(&env, 86400 * 4 - 3600);
+    let result = client.charge(&config.admin, &vec![&env, affected_id]);
+    assert_eq!(result.charges, vec![&env, (affected_id, fee)]);
+}
+
+#[test]
+fn source_outage_no_expiration_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    client.suspend_by_source(&String::from_str(&env, "source1"), &false, &0, &10);
+    client.suspend_by_source(&String::from_str(&env, "source2"), &false, &0, &10);
+
+    // Long outages don't expire the subscription
+    set_timestamp(&env, 86400 * 40);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::SourceOutage);
+    assert_eq!(subscription.balance, fee * 8);
+
+    // The subscription stays paused while any of its sources is offline
+    client.resume_by_source(&String::from_str(&env, "source2"), &0, &10);
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::SourceOutage
+    );
+    assert_eq!(client.active_count(), 0);
+
+    client.resume_by_source(&String::from_str(&env, "source1"), &0, &10);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+    assert_eq!(subscription.last_charged, 86400 * 40 * 1000);
+    assert_eq!(client.active_count(), 1);
+}
+
+#[test]
+fn schema_version_test() {
+    let (env, client, config) = init_contract_with_admin();
+    assert_eq!(client.schema_version(), SCHEMA_VERSION);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (current_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Simulate records left by a contract deployed before the schema versioning
+    let legacy = |balance: u64, status: SubscriptionStatus| LegacySubscription {
+        owner: owner.clone(),
+        base: params.base.clone(),
+        quote: params.quote.clone(),
+        threshold: 5,
+        heartbeat: 10,
+        webhook: params.webhook.clone(),
+        balance,
+        status,
+        updated: 1000 * 1000,
+    };
+    env.as_contract(&client.address, || {
+        env.storage()
+            .persistent()
+            .set(&2u64, &legacy(fee * 5, SubscriptionStatus::Active));
+        env.storage()
+            .persistent()
+            .set(&3u64, &legacy(fee / 2, SubscriptionStatus::Suspended));
+        env.set_last_subscription_id(3);
+        env.set_schema_version(0);
+    });
+    assert_eq!(client.schema_version(), 0);
+
+    // Contract is unavailable until migrated
+    let result = client.try_get_subscription(&current_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::MigrationRequired)))
+    );
+    // Non-panicking reads report legacy records instead of trapping
+    assert!(!client.is_owner(&2, &owner));
+    assert!(!client.would_trigger(&2, &100));
+    assert!(!client.check_invariants(&2));
+
+    // Migrate in pages
+    assert_eq!(client.migrate(&0, &2), 2);
+    assert_eq!(client.schema_version(), 0);
+    assert_eq!(client.migrate(&2, &2), 0);
+    assert_eq!(client.schema_version(), SCHEMA_VERSION);
+
+    // Legacy records are rewritten in the current layout
+    let migrated = client.get_subscription(&2);
+    assert_eq!(migrated.threshold, ThresholdKind::Relative(5));
+    assert_eq!(migrated.heartbeat, 10);
+    assert_eq!(migrated.heartbeat_unit, HeartbeatUnit::Minutes);
+    assert_eq!(migrated.balance, fee * 5);
+    assert_eq!(migrated.last_charged, 1000 * 1000);
+    assert_eq!(migrated.suspended_at, None);
+    let migrated = client.get_subscription(&3);
+    assert_eq!(migrated.status, SubscriptionStatus::Suspended);
+    assert_eq!(migrated.suspended_at, Some(1000 * 1000));
+    env.as_contract(&client.address, || {
+        assert_eq!(env.get_legacy_subscription(2), None);
+    });
+    // Migrated subscriptions are counted and indexed
+    assert_eq!(client.subscription_count(), 3);
+    assert_eq!(client.active_count(), 2);
+    assert_eq!(client.total_locked(), fee * 8 + fee * 5 + fee / 2);
+    assert_eq!(
+        client.subscriptions_for_pair(&params.base, &params.quote, &10, &0),
+        vec![&env, current_id, 2, 3]
+    );
+    assert_eq!(
+        client.get_owner_subscriptions_full(&owner, &0, &10).len(),
+        3
+    );
+
+    // Migration is a no-op once the storage is up to date
+    assert_eq!(client.migrate(&0, &2), 0);
+    assert_eq!(client.subscription_count(), 3);
+}
+
+#[test]
+fn mass_suspended_event_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    for _ in 0..3 {
+        client.create_subscription(&params, &(fee * 3), &None);
+    }
+
+    // The source index is processed in pages
+    let cursor = client.suspend_by_source(&String::from_str(&env, "source1"), &false, &0, &2);
+    assert_eq!(cursor, 2);
+    assert_eq!(client.active_count(), 1);
+    // The summary is published only once the whole index is processed
+    assert_eq!(
+        count_events(&env, &client.address, Symbol::new(&env, "mass_suspended")),
+        0
+    );
+
+    let cursor = client.suspend_by_source(&String::from_str(&env, "source1"), &false, &cursor, &2);
+    assert_eq!(cursor, 0);
+    let (_, topics, data) = env.events().all().last().unwrap();
+    assert_eq!(
+        Symbol::try_from_val(&env, &topics.get_unchecked(2)),
+        Ok(Symbol::new(&env, "mass_suspended"))
+    );
+    assert_eq!(
+        <(String, u32)>::try_from_val(&env, &data).unwrap(),
+        (String::from_str(&env, "source1"), 3)
+    );
+    // Per-subscription events are not published when disabled
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("suspended")),
+        0
+    );
+    assert_eq!(client.active_count(), 0);
+}
+
+#[test]
+fn fee_history_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    set_timestamp(&env, 1000);
+    client.set_fee(&(config.fee * 2));
+    set_timestamp(&env, 2000);
+    client.set_fee(&(config.fee * 3));
+
+    assert_eq!(
+        client.fee_history(),
+        vec![
+            &env,
+            (0, config.fee),
+            (1000 * 1000, config.fee * 2),
+            (2000 * 1000, config.fee * 3)
+        ]
+    );
+    assert_eq!(client.fee_at(&0), config.fee);
+    assert_eq!(client.fee_at(&(999 * 1000)), config.fee);
+    assert_eq!(client.fee_at(&(1000 * 1000)), config.fee * 2);
+    assert_eq!(client.fee_at(&(1500 * 1000)), config.fee * 2);
+    assert_eq!(client.fee_at(&(5000 * 1000)), config.fee * 3);
+
+    // History is bounded, the oldest entries are evicted
+    for i in 0..MAX_FEE_HISTORY {
+        set_timestamp(&env, 3000 + i as u64);
+        client.set_fee(&(config.fee + i as u64));
+    }
+    let history = client.fee_history();
+    assert_eq!(history.len(), MAX_FEE_HISTORY);
+    assert_eq!(history.first().unwrap(), (3000 * 1000, config.fee));
+}
+
+#[test]
+fn insufficient_revival_deposit_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Suspended);
+
+    // Revival fee alone is not enough
+    let result = client.try_deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InsufficientRevivalDeposit)))
+    );
+    assert_eq!(client.get_subscription(&subscription_id), subscription);
+
+    // Residual balance counts towards the revival
+    client.deposit(&owner, &subscription_id, &(fee * 2 - fee / 2), &None);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+    assert_eq!(subscription.balance, fee);
+}
+
+#[test]
+fn extend_ttls_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (first_id, _) = client.create_subscription(&params, &(fee * 5), &None);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 4), &None);
+    let get_ttl = |subscription_id: u64| {
+        env.as_contract(&client.address, || {
+            env.storage().persistent().get_ttl(&subscription_id)
+        })
+    };
+    assert_eq!(get_ttl(first_id), 17280 * 3);
+    assert_eq!(get_ttl(second_id), 17280 * 2);
+
+    // TTLs drift low as ledgers advance
+    let ledger_info = env.ledger().get();
+    env.ledger().set(LedgerInfo {
+        sequence_number: ledger_info.sequence_number + 3000,
+        ..ledger_info
+    });
+    assert_eq!(get_ttl(first_id), 17280 * 3 - 3000);
+    assert_eq!(get_ttl(second_id), 17280 * 2 - 3000);
+
+    // Missing IDs are skipped
+    client.extend_ttls(&vec![&env, first_id, 100, second_id]);
+    assert_eq!(get_ttl(first_id), 17280 * 3);
+    assert_eq!(get_ttl(second_id), 17280 * 2);
+
+    // Batch size is capped
+    let mut ids = Vec::new(&env);
+    for id in 0..=MAX_BATCH_SIZE as u64 {
+        ids.push_back(id);
+    }
+    let result = client.try_extend_ttls(&ids);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+}
+
+#[test]
+fn extend_index_ttls_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    // Free subscriptions are kept for the maximum TTL
+    client.set_free_tier(&vec![
+        &env,
+        (
+            params.base.clone(),
+            params.quote.clone(),
+            params.heartbeat.unwrap(),
+        ),
+    ]);
+    let (subscription_id, _) = client.create_subscription(&params, &fee, &None);
+    let max_ttl = env.as_contract(&client.address, || env.storage().max_ttl());
+    let pair_key: BytesN<32> = env
+        .crypto()
+        .sha256(&(params.base.clone(), params.quote.clone()).to_xdr(&env))
+        .into();
+    let get_ttls = || {
+        env.as_contract(&client.address, || {
+            let storage = env.storage().persistent();
+            (
+                storage.get_ttl(&subscription_id),
+                storage.get_ttl(&(("pair", pair_key.clone()), subscription_id)),
+                storage.get_ttl(&(("owner", owner.clone()), subscription_id)),
+                storage.get_ttl(&(("source", params.base.source.clone()), subscription_id)),
+            )
+        })
+    };
+    assert_eq!(get_ttls(), (max_ttl, max_ttl, max_ttl, max_ttl));
+
+    let ledger_info = env.ledger().get();
+    env.ledger().set(LedgerInfo {
+        sequence_number: ledger_info.sequence_number + 3000,
+        ..ledger_info
+    });
+    let ttl = max_ttl - 3000;
+    assert_eq!(get_ttls(), (ttl, ttl, ttl, ttl));
+
+    // Index entries are extended along with the record
+    client.extend_ttls(&vec![&env, subscription_id]);
+    assert_eq!(get_ttls(), (max_ttl, max_ttl, max_ttl, max_ttl));
+}
+
+#[test]
+fn projected_suspension_at_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    set_timestamp(&env, 1000);
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 7 + fee / 2), &None);
+    assert_eq!(
+        client.projected_suspension_at(&subscription_id),
+        1000 * 1000 + 5 * DAY
+    );
+
+    // Projection matches the actual suspension
+    set_timestamp(&env, 1000 + 86400 * 5);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.suspended, vec![&env, subscription_id]);
+    assert_eq!(client.projected_suspension_at(&subscription_id), 0);
+}
+
+#[test]
+fn min_deposit_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3), &None);
+    assert_eq!(client.min_deposit(), 0);
+    client.deposit(&owner, &subscription_id, &1, &None);
+
+    client.set_min_deposit(&fee);
+    assert_eq!(client.get_full_config().min_deposit, fee);
+    let result = client.try_deposit(&owner, &subscription_id, &(fee - 1), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::DepositTooSmall)))
+    );
+
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(
+        client.get_subscription(&subscription_id).balance,
+        fee * 2 + 1
+    );
+}
+
+#[test]
+fn create_subscription_for_days_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+
+    let (subscription_id, subscription) = client.create_subscription_for_days(&params, &30);
+    assert_eq!(subscription.balance, fee * 30);
+    assert_eq!(token.balance(&owner), owner_balance - (fee * 32) as i128);
+    let (_, runway_days, _, _) = client.subscription_economics(&subscription_id);
+    assert_eq!(runway_days, 30);
+
+    // Zero days is rejected
+    let result = client.try_create_subscription_for_days(&params, &0);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+}
+
+#[test]
+fn fees_burned_between_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Charge across day boundaries
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+
+    assert_eq!(client.fees_burned_between(&0, &0), fee * 2);
+    assert_eq!(client.fees_burned_between(&1, &2), fee * 2);
+    assert_eq!(client.fees_burned_between(&0, &2), fee * 4);
+    assert_eq!(client.fees_burned_between(&3, &10), 0);
+
+    // Buckets past the retention window are evicted
+    set_timestamp(&env, 86400 * 95);
+    client.create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(client.fees_burned_between(&0, &95), fee * 2);
+    assert_eq!(client.fees_burned_between(&95, &95), fee * 2);
+}
+
+#[test]
+fn reduce_cost_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    assert_eq!(client.get_retention_fee(&subscription_id), fee);
+
+    // Lower cadence reduces the retention fee
+    client.reduce_cost(&subscription_id, &60);
+    assert_eq!(client.get_subscription(&subscription_id).heartbeat, 60);
+    let reduced_fee = calc_fee(config.fee, &params.base, &params.quote, 60);
+    assert!(reduced_fee < fee);
+    assert_eq!(client.get_retention_fee(&subscription_id), reduced_fee);
+
+    // Same heartbeat is allowed, higher cadence is rejected
+    client.reduce_cost(&subscription_id, &60);
+    let result = client.try_reduce_cost(&subscription_id, &30);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidHeartbeat)))
+    );
+    assert_eq!(client.get_subscription(&subscription_id).heartbeat, 60);
+}
+
+#[test]
+fn reduce_cost_free_tier_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    client.set_free_tier(&vec![&env, (params.base.clone(), params.quote.clone(), 60)]);
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Allowlisted heartbeat makes the subscription free after settling the accrued days
+    set_timestamp(&env, 86400);
+    client.reduce_cost(&subscription_id, &60);
+    let subscription = client.get_subscription(&subscription_id);
+    assert!(subscription.free_tier);
+    assert_eq!(subscription.balance, fee * 7);
+    set_timestamp(&env, 86400 * 3);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.charges.len(), 0);
+
+    // Leaving the allowlisted heartbeat restores charging from the change time
+    client.reduce_cost(&subscription_id, &120);
+    let subscription = client.get_subscription(&subscription_id);
+    assert!(!subscription.free_tier);
+    assert_eq!(subscription.last_charged, 86400 * 3 * 1000);
+    let reduced_fee = calc_fee(config.fee, &params.base, &params.quote, 120);
+    set_timestamp(&env, 86400 * 4);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.charges, vec![&env, (subscription_id, reduced_fee)]);
+
+    // Free tier slot of the owner is released
+    let mut free_params = params.clone();
+    free_params.heartbeat = Some(60);
+    let (_, subscription) = client.create_subscription(&free_params, &fee, &None);
+    assert!(subscription.free_tier);
+}
+
+#[test]
+fn reduce_cost_settlement_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Days used before the change are charged at the previous fee
+    set_timestamp(&env, 86400 * 3);
+    client.reduce_cost(&subscription_id, &60);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.balance, fee * 5);
+    assert_eq!(subscription.last_charged, 86400 * 3 * 1000);
+    assert_eq!(client.total_locked(), fee * 5);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("charged")),
+        1
+    );
+
+    // Only the following days are charged at the reduced fee
+    let reduced_fee = calc_fee(config.fee, &params.base, &params.quote, 60);
+    set_timestamp(&env, 86400 * 4);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.charges, vec![&env, (subscription_id, reduced_fee)]);
+}
+
+#[test]
+fn complexity_factor_read_test() {
+    let (env, client, _) = init_contract_with_admin();
+
+    let asset = |symbol: &str, source: &str| TickerAsset {
+        asset: String::from_str(&env, symbol),
+        source: String::from_str(&env, source),
+    };
+    assert_eq!(
+        client.complexity_factor(&asset("BTC", "source1"), &asset("ETH", "source1")),
+        1
+    );
+    assert_eq!(
+        client.complexity_factor(&asset("BTC", "source1"), &asset("ETH", "source2")),
+        2
+    );
+}
+
+#[test]
+fn source_allowlist_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+
+    // Empty allowlist doesn't restrict sources
+    assert_eq!(client.sources().len(), 0);
+    client.create_subscription(&params, &(fee * 4), &None);
+
+    let source1 = String::from_str(&env, "source1");
+    let source2 = String::from_str(&env, "source2");
+    client.add_source(&source1);
+    client.add_source(&source1);
+    assert_eq!(client.sources(), vec![&env, source1.clone()]);
+
+    // Quote source is not allowlisted
+    let result = client.try_create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::UnknownSource))));
+
+    client.add_source(&source2);
+    client.create_subscription(&params, &(fee * 4), &None);
+
+    client.remove_source(&source2);
+    assert_eq!(client.sources(), vec![&env, source1]);
+    let result = client.try_create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::UnknownSource))));
+}
+
+#[test]
+fn charge_compact_test() {
+    // Charge the same set of subscriptions in two identical contracts
+    let setup = || {
+        let (env, client, config) = init_contract_with_admin();
+        let owner = generate_funded_owner(&env, &config);
+        let params = generate_subscription_params(&env, &owner);
+        let fee = calc_fee(
+            config.fee,
+            &params.base,
+            &params.quote,
+            params.heartbeat.unwrap(),
+        );
+        let (funded_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+        let (insolvent_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+        set_timestamp(&env, 86400);
+        let ids = vec![&env, funded_id, insolvent_id];
+        (env, client, config, ids)
+    };
+
+    let (_, detailed_client, config, ids) = setup();
+    let detailed = detailed_client.charge(&config.admin, &ids);
+    let (_, compact_client, config, ids) = setup();
+    let (total_charged, suspended) = compact_client.charge_compact(&config.admin, &ids);
+
+    let detailed_total: u64 = detailed.charges.iter().map(|(_, amount)| amount).sum();
+    assert!(total_charged > 0);
+    assert_eq!(total_charged, detailed_total);
+    assert_eq!(
+        suspended.iter().collect::<std::vec::Vec<_>>(),
+        detailed.suspended.iter().collect::<std::vec::Vec<_>>()
+    );
+    assert_eq!(suspended.len(), 1);
+}
+
+#[test]
+fn auto_cancel_on_suspend_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+    client.set_auto_cancel(&subscription_id, &true);
+    assert!(
+        client
+            .get_subscription(&subscription_id)
+            .auto_cancel_on_suspend
+    );
+
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+
+    // Charge to insolvency cancels the subscription and refunds the residual
+    set_timestamp(&env, 86400);
+    let result = client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.charges, vec![&env, (subscription_id, fee)]);
+    assert_eq!(result.suspended.len(), 0);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("cancelled")),
+        1
+    );
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .find(|(_, topics, _)| {
+            Symbol::try_from_val(&env, &topics.get_unchecked(2)) == Ok(symbol_short!("cancelled"))
+        })
+        .unwrap();
+    assert_eq!(
+        <(u64, u64, bool, u32)>::try_from_val(&env, &data).unwrap(),
+        (subscription_id, 0, true, 7)
+    );
+    assert_eq!(token.balance(&owner), owner_balance + (fee / 2) as i128);
+    assert_eq!(client.subscription_count(), 0);
+    assert_eq!(client.active_count(), 0);
+    assert_eq!(client.total_locked(), 0);
+    let result = client.try_get_subscription(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+}
+
+#[test]
+fn next_id_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    assert_eq!(client.next_id(), client.last_id() + 1);
+
+    // Reserved system range is skipped
+    client.set_user_id_start(&100);
+    assert_eq!(client.next_id(), 100);
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(subscription_id, 100);
+    assert_eq!(client.next_id(), 101);
+}
+
+#[test]
+fn token_decimals_in_events_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let decimals = client.token_decimals();
+    assert_eq!(decimals, TokenClient::new(&env, &config.token).decimals());
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 4), &None);
+
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    let (_, _, data) = env.events().all().last().unwrap();
+    let (_, _, amount, event_decimals) =
+        <(u64, Subscription, u64, u32)>::try_from_val(&env, &data).unwrap();
+    assert_eq!((amount, event_decimals), (fee, decimals));
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    let (_, _, data) = env
+        .events()
+        .all()
+        .iter()
+        .find(|(_, topics, _)| {
+            Symbol::try_from_val(&env, &topics.get_unchecked(2)) == Ok(symbol_short!("charged"))
+        })
+        .unwrap();
+    let (_, charge, _, event_decimals) = <(u64, u64, u64, u32)>::try_from_val(&env, &data).unwrap();
+    assert_eq!((charge, event_decimals), (fee, decimals));
+}
+
+#[test]
+fn recompute_aggregates_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (first_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let (second_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+    let (third_id, _) = client.create_subscription(&params, &(fee * 6), &None);
+    let mut nonce_params = params.clone();
+    nonce_params.id_nonce = Some(7);
+    let (derived_id, _) = client.create_subscription(&nonce_params, &(fee * 4), &None);
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, first_id, second_id, third_id]);
+    assert_eq!(
+        client.get_subscription(&second_id).status,
+        SubscriptionStatus::Suspended
+    );
+    // Move a subscription out of the sequential ID range
+    client.remap_id(&second_id, &100);
+
+    let expected = (
+        client.subscription_count(),
+        client.active_count(),
+        client.total_locked(),
+    );
+    assert_eq!(expected, (4, 3, fee * 12 + fee / 2));
+
+    // Corrupt the counters
+    env.as_contract(&client.address, || {
+        env.set_active_count(42);
+        env.set_total_locked(1);
+    });
+
+    // Nothing to commit before the rebuild starts
+    let result = client.try_finalize_aggregates();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::AggregatesNotRecomputed)))
+    );
+
+    // Rebuild in pages, derived and remapped IDs are scanned after the sequential range
+    let cursor = client.recompute_aggregates(&0, &2);
+    assert_eq!(cursor, 2);
+    // Out of order page is rejected
+    let result = client.try_recompute_aggregates(&1, &2);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidIdRange)))
+    );
+    let cursor = client.recompute_aggregates(&cursor, &2);
+    assert_eq!(cursor, derived_id);
+    // Incomplete rebuild can't be committed
+    let result = client.try_finalize_aggregates();
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::AggregatesNotRecomputed)))
+    );
+    let cursor = client.recompute_aggregates(&cursor, &2);
+    assert_eq!(cursor, 0);
+    client.finalize_aggregates();
+
+    assert_eq!(
+        (
+            client.subscription_count(),
+            client.active_count(),
+            client.total_locked()
+        ),
+        expected
+    );
+}
+
+#[test]
+fn subscription_defaults_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    params.heartbeat = None;
+    params.threshold = ThresholdParam::Default;
+
+    // Defaults are required when the params omit them
+    let result = client.try_create_subscription(&params, &100_0000000, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidHeartbeat)))
+    );
+
+    // Defaults are validated
+    let result = client.try_set_defaults(&1, &ThresholdKind::Relative(10));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidHeartbeat)))
+    );
+    let result = client.try_set_defaults(&60, &ThresholdKind::Relative(0));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidThreshold)))
+    );
+
+    client.set_defaults(&60, &ThresholdKind::Relative(50));
+    assert_eq!(client.defaults(), Some((60, ThresholdKind::Relative(50))));
+
+    // Defaults are applied
+    let fee = calc_fee(config.fee, &params.base, &params.quote, 60);
+    let (_, subscription) = client.create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(subscription.heartbeat, 60);
+    assert_eq!(subscription.threshold, ThresholdKind::Relative(50));
+    assert_eq!(subscription.balance, fee * 2);
+
+    // Explicit values take precedence
+    params.heartbeat = Some(5);
+    params.threshold = ThresholdParam::Custom(ThresholdKind::Absolute(100));
+    let fee = calc_fee(config.fee, &params.base, &params.quote, 5);
+    let (_, subscription) = client.create_subscription(&params, &(fee * 4), &None);
+    assert_eq!(subscription.heartbeat, 5);
+    assert_eq!(subscription.threshold, ThresholdKind::Absolute(100));
+}
+
+#[test]
+fn initialized_test() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register_contract(None, SubscriptionContract);
+    let client = SubscriptionContractClient::new(&env, &contract_id);
+    assert!(!client.initialized());
+
+    let admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract_v2(admin.clone());
+    client.config(&ContractConfig {
+        admin,
+        token: token.address(),
+        init_token: None,
+        fee: 100,
+        features: Vec::new(&env),
+    });
+    assert!(client.initialized());
+}
+
+#[test]
+fn deposit_max_fee_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 3 + fee / 2), &None);
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Suspended
+    );
+
+    // Fee is raised after the owner signed the revival deposit
+    client.set_fee(&(config.fee * 2));
+    let new_fee = client.get_retention_fee(&subscription_id);
+    assert!(new_fee > fee);
+    let result = client.try_deposit(&owner, &subscription_id, &(fee * 3), &Some(fee));
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::FeeChangedError)))
+    );
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Suspended
+    );
+
+    // Deposit accepting the current fee revives the subscription
+    client.deposit(&owner, &subscription_id, &(new_fee * 3), &Some(new_fee));
+    assert_eq!(
+        client.get_subscription(&subscription_id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn last_triggered_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let (first_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+    let (second_id, _) = client.create_subscription(&params, &(config.fee * 100), &None);
+    let leaf = BytesN::from_array(&env, &[1; 32]);
+
+    client.trigger_subscriptions(
+        &config.admin,
+        &1000u64,
+        &vec![&env, (first_id, leaf.clone())],
+    );
+    assert_eq!(
+        client.get_subscription(&first_id).last_triggered,
+        Some(1000)
+    );
+
+    client.trigger_subscriptions(&config.admin, &2000u64, &vec![&env, (first_id, leaf)]);
+    assert_eq!(
+        client.get_subscription(&first_id).last_triggered,
+        Some(2000)
+    );
+
+    // Never triggered subscription has no trigger timestamp
+    assert_eq!(client.get_subscription(&second_id).last_triggered, None);
+}
+
+#[test]
+fn soft_cancel_restore_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    set_timestamp(&env, 1000);
+    client.soft_cancel(&subscription_id);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::PendingDeletion);
+    assert_eq!(subscription.cancelled_at, Some(1000 * 1000));
+    assert_eq!(client.active_count(), 0);
+    assert!(client.check_invariants(&subscription_id));
+
+    // Pending subscriptions are not billed
+    set_timestamp(&env, 86400 * 2);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 8);
+
+    // Finalization is not possible within the recovery window
+    let result = client.try_finalize_cancel(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimestamp)))
+    );
+
+    client.restore(&subscription_id);
+    let subscription = client.get_subscription(&subscription_id);
+    assert_eq!(subscription.status, SubscriptionStatus::Active);
+    assert_eq!(subscription.cancelled_at, None);
+    // Billing resumes without the pending deletion period
+    assert_eq!(subscription.last_charged, (86400 * 2 - 1000) * 1000);
+    assert_eq!(client.active_count(), 1);
+
+    // Only pending subscriptions can be restored
+    let result = client.try_restore(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidSubscriptionStatusError)))
+    );
+}
+
+#[test]
+fn soft_cancel_restore_accrual_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Cancelling and restoring right before the charge doesn't forgive the accrued time
+    set_timestamp(&env, 86400 - 10);
+    client.soft_cancel(&subscription_id);
+    client.restore(&subscription_id);
+    assert_eq!(client.get_subscription(&subscription_id).last_charged, 0);
+
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(client.get_subscription(&subscription_id).balance, fee * 7);
+}
+
+#[test]
+fn soft_cancel_finalize_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    client.set_recovery_window(&(86400 * 1000));
+    assert_eq!(client.recovery_window(), 86400 * 1000);
+
+    client.soft_cancel(&subscription_id);
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+
+    // Restoring is not possible after the recovery window
+    set_timestamp(&env, 86400);
+    let result = client.try_restore(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::InvalidTimestamp)))
+    );
+
+    client.finalize_cancel(&subscription_id);
+    assert_eq!(token.balance(&owner), owner_balance + (fee * 8) as i128);
+    assert_eq!(client.subscription_count(), 0);
+    assert_eq!(client.total_locked(), 0);
+    let result = client.try_get_subscription(&subscription_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+}
+
+#[test]
+fn daily_run_rate_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let mut params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    client.create_subscription(&params, &(fee * 10), &None);
+    client.create_subscription(&params, &(fee * 10), &None);
+    params.heartbeat = Some(60);
+    let hourly_fee = calc_fee(config.fee, &params.base, &params.quote, 60);
+    client.create_subscription(&params, &(hourly_fee * 10), &None);
+    // Suspended subscription is not included
+    let (suspended_id, _) = client.create_subscription(&params, &(hourly_fee * 3), &None);
+    // Subscription with a derived ID is scanned after the sequential range
+    params.id_nonce = Some(1);
+    client.create_subscription(&params, &(hourly_fee * 10), &None);
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, suspended_id]);
+
+    let expected = fee * 2 + hourly_fee * 2;
+    assert_eq!(client.daily_run_rate(&0, &10), (expected, 0));
+    // Pages are summed by the caller, following the returned cursor
+    let (first_page, cursor) = client.daily_run_rate(&0, &2);
+    assert_eq!(cursor, 2);
+    let (second_page, cursor) = client.daily_run_rate(&cursor, &2);
+    assert_eq!(cursor, 4);
+    let (third_page, cursor) = client.daily_run_rate(&cursor, &2);
+    assert_eq!(cursor, 0);
+    assert_eq!(first_page + second_page + third_page, expected);
+    // Zero limit doesn't return a cursor to loop on
+    assert_eq!(client.daily_run_rate(&2, &0), (0, 0));
+}
+
+#[test]
+fn asset_tier_init_multiplier_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let mut high_tier_params = params.clone();
+    high_tier_params.base.asset = String::from_str(&env, "XAU");
+    client.set_asset_tiers(&vec![&env, (high_tier_params.base.clone(), 5)]);
+    assert_eq!(client.asset_tiers().len(), 1);
+
+    // Default tier uses the global multiplier
+    assert_eq!(client.init_multiplier(&params.base, &params.quote), 2);
+    let (_, subscription) = client.create_subscription(&params, &(fee * 10), &None);
+    assert_eq!(subscription.balance, fee * 8);
+
+    // High tier asset incurs a larger creation fee
+    assert_eq!(
+        client.init_multiplier(&high_tier_params.base, &high_tier_params.quote),
+        5
+    );
+    let (_, subscription) = client.create_subscription(&high_tier_params, &(fee * 10), &None);
+    assert_eq!(subscription.balance, fee * 5);
+
+    // Zero and oversized multipliers are rejected
+    for multiplier in [0u32, 101] {
+        let result =
+            client.try_set_asset_tiers(&vec![&env, (high_tier_params.base.clone(), multiplier)]);
+        assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+    }
+    client.set_asset_tiers(&vec![&env, (high_tier_params.base.clone(), 100)]);
+    assert_eq!(
+        client.init_multiplier(&high_tier_params.base, &high_tier_params.quote),
+        100
+    );
+}
+
+#[test]
+fn split_subscription_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (source_id, _) = client.create_subscription(&params, &(fee * 20), &None);
+    let token = TokenClient::new(&env, &config.token);
+    let owner_balance = token.balance(&owner);
+
+    // Track the same pair with a different heartbeat
+    let mut new_params = params.clone();
+    new_params.heartbeat = Some(60);
+    let new_fee = calc_fee(config.fee, &params.base, &params.quote, 60);
+    let (new_id, subscription) = client.split_subscription(&source_id, &(fee * 10), &new_params);
+    assert_eq!(subscription.heartbeat, 60);
+    assert_eq!(subscription.owner, owner);
+    assert_eq!(subscription.balance, fee * 10 - new_fee * 2);
+    assert_eq!(client.get_subscription(&source_id).balance, fee * 8);
+    // Owner tokens are not touched
+    assert_eq!(token.balance(&owner), owner_balance);
+    assert_eq!(client.subscription_count(), 2);
+    assert_eq!(client.total_locked(), fee * 18 - new_fee * 2);
+    assert_ne!(new_id, source_id);
+
+    // Source has to keep at least one day of runway
+    let result = client.try_split_subscription(&source_id, &(fee * 7 + 1), &new_params);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+    assert_eq!(client.get_subscription(&source_id).balance, fee * 8);
+
+    // Days accrued by the source are settled before the runway check
+    set_timestamp(&env, 86400 * 3);
+    let result = client.try_split_subscription(&source_id, &(fee * 5), &new_params);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+    client.split_subscription(&source_id, &(fee * 4), &new_params);
+    let source = client.get_subscription(&source_id);
+    assert_eq!(source.balance, fee);
+    assert_eq!(source.last_charged, 86400 * 3 * 1000);
+
+    // Free tier subscriptions can't move the reserve away
+    client.set_free_tier(&vec![
+        &env,
+        (
+            params.base.clone(),
+            params.quote.clone(),
+            params.heartbeat.unwrap(),
+        ),
+    ]);
+    let free_owner = generate_funded_owner(&env, &config);
+    let mut free_params = generate_subscription_params(&env, &free_owner);
+    free_params.reserve = fee;
+    let (free_id, subscription) = client.create_subscription(&free_params, &(fee * 3), &None);
+    assert!(subscription.free_tier);
+    let mut new_params = free_params.clone();
+    new_params.heartbeat = Some(60);
+    new_params.reserve = 0;
+    let result = client.try_split_subscription(&free_id, &(fee * 3), &new_params);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::InvalidAmount))));
+    assert_eq!(client.get_subscription(&free_id).balance, fee * 3);
+}
+
+#[test]
+fn merge_subscriptions_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (into_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let mut from_params = params.clone();
+    from_params.reserve = fee;
+    let (from_id, _) = client.create_subscription(&from_params, &(fee * 5), &None);
+    let total_locked = client.total_locked();
+
+    // Days accrued by the merged subscription are charged, the reserve is carried over
+    set_timestamp(&env, 86400);
+    client.merge_subscriptions(&into_id, &from_id);
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("merged")),
+        1
+    );
+    assert_eq!(
+        count_events(&env, &client.address, symbol_short!("charged")),
+        1
+    );
+    let into = client.get_subscription(&into_id);
+    assert_eq!(into.balance, fee * 10);
+    assert_eq!(into.reserve, fee);
+    assert_eq!(client.subscription_count(), 1);
+    assert_eq!(client.active_count(), 1);
+    assert_eq!(client.total_locked(), total_locked - fee);
+    let result = client.try_get_subscription(&from_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::SubscriptionNotFound)))
+    );
+}
+
+#[test]
+fn merge_mismatched_subscriptions_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (into_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let mut other_params = params.clone();
+    other_params.heartbeat = Some(60);
+    let (from_id, _) = client.create_subscription(&other_params, &(fee * 5), &None);
+
+    let result = client.try_merge_subscriptions(&into_id, &from_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::ParamsMismatch)))
+    );
+
+    // Subscriptions of different owners can't be merged
+    let other_owner = generate_funded_owner(&env, &config);
+    let (other_id, _) = client.create_subscription(
+        &generate_subscription_params(&env, &other_owner),
+        &(fee * 5),
+        &None,
+    );
+    let result = client.try_merge_subscriptions(&into_id, &other_id);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::Unauthorized))));
+    assert_eq!(client.subscription_count(), 3);
+}
+
+#[test]
+fn fee_rounding_test() {
+    let env = Env::default();
+    let source1_asset = TickerAsset {
+        asset: String::from_str(&env, "BTC"),
+        source: String::from_str(&env, "source1"),
+    };
+
+    let source2_asset = TickerAsset {
+        asset: String::from_str(&env, "ETH"),
+        source: String::from_str(&env, "source2"),
+    };
+
+    let test_cases = [
+        (
+            100000000,
+            &source1_asset,
+            &source2_asset,
+            300,
+            979795896,
+            979795897,
+        ), // Cross-price, rounded once
+        (
+            100000000,
+            &source1_asset,
+            &source1_asset,
+            300,
+            489897948,
+            489897949,
+        ), // Fraction above half
+        (
+            500000000,
+            &source1_asset,
+            &source1_asset,
+            600,
+            1732050807,
+            1732050808,
+        ), // Large base fee
+        (
+            100000000,
+            &source1_asset,
+            &source1_asset,
+            7200,
+            100000000,
+            100000000,
+        ), // Reference heartbeat
+        (
+            100000000,
+            &source1_asset,
+            &source2_asset,
+            60000,
+            200000000,
+            200000000,
+        ), // Min fee applied
+        (1, &source1_asset, &source1_asset, 3200, 1, 2), // sqrt(2.25) = 1.5 is rounded up
+    ];
+
+    for (i, &(base_fee, base, quote, heartbeat_seconds, floor_fee, half_up_fee)) in
+        test_cases.iter().enumerate()
+    {
+        assert_eq!(
+            calc_fee_rounded(
+                base_fee,
+                base,
+                quote,
+                heartbeat_seconds,
+                RoundingMode::Floor
+            ),
+            floor_fee,
+            "Test case {} failed",
+            i
+        );
+        assert_eq!(
+            calc_fee_rounded(
+                base_fee,
+                base,
+                quote,
+                heartbeat_seconds,
+                RoundingMode::HalfUp
+            ),
+            half_up_fee,
+            "Test case {} failed",
+            i
+        );
+        // Floor mode matches the default fee calculation
+        assert_eq!(
+            calc_fee_seconds(base_fee, base, quote, heartbeat_seconds),
+            floor_fee
+        );
+    }
+}
+
+#[test]
+fn rounding_mode_test() {
+    let (env, client, _) = init_contract_with_admin();
+
+    let owner = Address::generate(&env);
+    let params = generate_subscription_params(&env, &owner);
+    let heartbeats = vec![&env, 5u32];
+    assert_eq!(client.rounding_mode(), RoundingMode::Floor);
+    let floor_fee = client
+        .fee_curve(&params.base, &params.quote, &heartbeats)
+        .get_unchecked(0);
+
+    client.set_rounding_mode(&RoundingMode::HalfUp);
+    assert_eq!(client.rounding_mode(), RoundingMode::HalfUp);
+    assert_eq!(client.get_full_config().rounding_mode, RoundingMode::HalfUp);
+    let half_up_fee = client
+        .fee_curve(&params.base, &params.quote, &heartbeats)
+        .get_unchecked(0);
+    assert_eq!(half_up_fee, floor_fee + 1);
+}
+
+#[test]
+fn get_statuses_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (active_id, _) = client.create_subscription(&params, &(fee * 100), &None);
+    let (suspended_id, _) = client.create_subscription(&params, &(fee * 2), &None);
+
+    // Charge more days than the second subscription balance can cover
+    set_timestamp(&env, 86400 * 3);
+    client.charge(&config.admin, &vec![&env, active_id, suspended_id]);
+
+    let statuses = client.get_statuses(&vec![&env, suspended_id, 100, active_id]);
+    assert_eq!(
+        statuses,
+        vec![
+            &env,
+            Some(SubscriptionStatus::Suspended),
+            None,
+            Some(SubscriptionStatus::Active)
+        ]
+    );
+
+    let mut ids = Vec::new(&env);
+    for id in 0..=MAX_BATCH_SIZE as u64 {
+        ids.push_back(id);
+    }
+    let result = client.try_get_statuses(&ids);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::TooManyItems))));
+}
+
+#[test]
+fn independent_pause_flags_test() {
+    let (env, client, config) = init_contract_with_admin();
+
+    let owner = generate_funded_owner(&env, &config);
+    let params = generate_subscription_params(&env, &owner);
+    let fee = calc_fee(
+        config.fee,
+        &params.base,
+        &params.quote,
+        params.heartbeat.unwrap(),
+    );
+    let (subscription_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+    let (other_id, _) = client.create_subscription(&params, &(fee * 10), &None);
+
+    // Paused deposits block funding, charges and cancellations still proceed
+    client.set_deposits_paused(&true);
+    assert!(client.deposits_paused());
+    assert!(!client.charges_paused());
+    assert!(client.get_full_config().deposits_paused);
+    let result = client.try_deposit(&owner, &subscription_id, &fee, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::DepositsPaused)))
+    );
+    let result = client.try_create_subscription(&params, &(fee * 10), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(contract_error(Error::DepositsPaused)))
+    );
+    set_timestamp(&env, 86400);
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    client.cancel(&other_id, &false);
+
+    // Paused charges block charging only
+    client.set_deposits_paused(&false);
+    client.set_charges_paused(&true);
+    assert!(!client.deposits_paused());
+    assert!(client.charges_paused());
+    set_timestamp(&env, 86400 * 2);
+    let result = client.try_charge(&config.admin, &vec![&env, subscription_id]);
+    assert_eq!(result.err(), Some(Ok(contract_error(Error::ChargesPaused))));
+    client.deposit(&owner, &subscription_id, &fee, &None);
+    client.create_subscription(&params, &(fee * 10), &None);
+
+    client.set_charges_paused(&false);
+    let balance = client.get_subscription(&subscription_id).balance;
+    client.charge(&config.admin, &vec![&env, subscription_id]);
+    assert!(client.get_subscription(&subscription_id).balance < balance);
+}
@@ -0,0 +1,12 @@
+use soroban_sdk::{contracttype, Vec};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+
+// Outcome of a charge batch
+pub struct ChargeResult {
+    // Charged subscription IDs with the amounts charged
+    pub charges: Vec<(u64, u64)>,
+    // IDs of subscriptions suspended by this charge
+    pub suspended: Vec<u64>,
+}
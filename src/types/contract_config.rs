@@ -1,4 +1,4 @@
-use soroban_sdk::{contracttype, Address};
+use soroban_sdk::{contracttype, Address, Symbol, Vec};
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -9,6 +9,10 @@ pub struct ContractConfig {
     pub admin: Address,
     // Retention fee token address
     pub token: Address,
+    // Optional creation fee token address, the retention fee token is used if not set
+    pub init_token: Option<Address>,
+    // Enabled capability flags
+    pub features: Vec<Symbol>,
     // Base contract fee amount
     pub fee: u64
 }
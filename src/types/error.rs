@@ -1,7 +1,8 @@
 use soroban_sdk::contracterror;
 
 #[contracterror]
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(clippy::enum_variant_names)]
 // Contract error codes
 pub enum Error {
     // Contract has been already initialized
@@ -21,5 +22,65 @@ pub enum Error {
     // Subscription webhook URL is too long
     WebhookTooLong = 7,
     // Current subscription status is not valid for the operation
-    InvalidSubscriptionStatusError = 8
+    InvalidSubscriptionStatusError = 8,
+    // Subscription with the derived ID already exists
+    IdCollision = 9,
+    // Timestamp is outside of the allowed range
+    InvalidTimestamp = 10,
+    // Reserve exceeds the subscription balance
+    InvalidReserve = 11,
+    // Token balance didn't change as expected after the transfer
+    TokenTransferFailed = 12,
+    // Number of items exceeds the allowed batch size
+    TooManyItems = 13,
+    // Trigger has been invoked sooner than the minimum interval allows
+    TriggerTooSoon = 14,
+    // Token address is not valid
+    InvalidToken = 15,
+    // Notification format is not supported
+    InvalidFormat = 16,
+    // Subscription ID is outside of the allowed range
+    InvalidIdRange = 17,
+    // Operation is not allowed while subscriptions exist
+    HasActiveSubscriptions = 18,
+    // Subscription webhook doesn't start with the expected ciphertext prefix
+    WebhookNotEncrypted = 19,
+    // Subscription has expired and can't be revived
+    SubscriptionExpired = 20,
+    // Encoded subscription data is malformed or has an unsupported layout version
+    InvalidSubscriptionData = 21,
+    // Base fee has been changed sooner than the minimum interval allows
+    FeeChangeTooSoon = 22,
+    // Subscription has no pending ownership transfer
+    NoPendingTransfer = 23,
+    // Contract is paused
+    ContractPaused = 24,
+    // Operation requires the contract to be paused
+    ContractNotPaused = 25,
+    // Notification window is incomplete or outside of a day
+    InvalidTimeWindow = 26,
+    // Owner has reached the maximum number of free tier subscriptions
+    FreeTierLimitReached = 27,
+    // Deposit doesn't cover the revival fee and one day of retention
+    InsufficientRevivalDeposit = 28,
+    // Deposit amount is below the configured minimum
+    DepositTooSmall = 29,
+    // Asset price source is not in the allowlist
+    UnknownSource = 30,
+    // Aggregate counters rebuild has not been started
+    AggregatesNotRecomputed = 31,
+    // Retention fee exceeds the maximum accepted by the caller
+    FeeChangedError = 32,
+    // Subscriptions track different pairs or have different notification params
+    ParamsMismatch = 33,
+    // Deposits are paused by the admin
+    DepositsPaused = 34,
+    // Charges are paused by the admin
+    ChargesPaused = 35,
+    // Contract has been retired permanently
+    ContractRetired = 36,
+    // Deferred burns have to be flushed first
+    PendingBurns = 37,
+    // Storage has to be migrated to the current schema first
+    MigrationRequired = 38,
 }
@@ -0,0 +1,68 @@
+use soroban_sdk::{contracttype, Address, Bytes, String, Symbol, Vec};
+
+use super::{
+    rounding_mode::RoundingMode, threshold_kind::ThresholdKind, ticker_asset::TickerAsset,
+};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+
+// Complete contract configuration snapshot
+pub struct FullConfig {
+    // Contract admin address
+    pub admin: Address,
+    // Worker account address
+    pub worker: Option<Address>,
+    // Retention fee token address
+    pub token: Address,
+    // Cached retention fee token decimals
+    pub token_decimals: u32,
+    // Creation fee token address, if it differs from the retention fee token
+    pub init_token: Option<Address>,
+    // Base contract fee amount
+    pub fee: u64,
+    // Minimum daily retention fee
+    pub min_fee_floor: u64,
+    // Minimum interval between base fee changes, in milliseconds
+    pub min_fee_change_interval: u64,
+    // Contract protocol version
+    pub protocol_version: u32,
+    // Minimum interval between triggers, in milliseconds
+    pub trigger_interval: u64,
+    // Whether charged fee burns are deferred
+    pub defer_burns: bool,
+    // Charged fees awaiting the deferred burn
+    pub pending_burn: u64,
+    // First subscription ID allocated to user subscriptions
+    pub user_id_start: u64,
+    // Maximum number of balance snapshots kept per subscription
+    pub balance_history_len: u32,
+    // Expected encrypted webhook prefix
+    pub webhook_prefix: Option<Bytes>,
+    // Enabled capability flags
+    pub features: Vec<Symbol>,
+    // Whether new subscriptions are blocked
+    pub paused: bool,
+    // Fee recipient receiving the treasury share instead of burning it
+    pub treasury: Option<Address>,
+    // Share of collected fees transferred to the treasury, in basis points
+    pub treasury_share_bps: u32,
+    // Minimum amount accepted by a deposit
+    pub min_deposit: u64,
+    // Period during which a soft-cancelled subscription can be restored, in milliseconds
+    pub recovery_window: u64,
+    // Rounding policy applied to calculated retention fees
+    pub rounding_mode: RoundingMode,
+    // Whether deposits and new subscriptions funding are blocked
+    pub deposits_paused: bool,
+    // Whether retention fee charges are blocked
+    pub charges_paused: bool,
+    // Allowed oracle sources, empty if any source is accepted
+    pub sources: Vec<String>,
+    // Default heartbeat and threshold, empty if not configured
+    pub defaults: Vec<(u32, ThresholdKind)>,
+    // Creation fee multipliers of tiered assets
+    pub asset_tiers: Vec<(TickerAsset, u32)>,
+    // Allowlisted free tier pairs with the heartbeats they have to match exactly
+    pub free_tier: Vec<(TickerAsset, TickerAsset, u32)>,
+}
@@ -0,0 +1,10 @@
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum HeartbeatUnit {
+    // Heartbeat is specified in minutes
+    Minutes = 0,
+    // Heartbeat is specified in seconds, used for sub-minute feeds
+    Seconds = 1
+}
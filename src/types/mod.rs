@@ -3,4 +3,12 @@ pub mod subscription;
 pub mod error;
 pub mod contract_config;
 pub mod subscription_init_params;
-pub mod subscription_status;
\ No newline at end of file
+pub mod subscription_status;
+pub mod heartbeat_unit;
+pub mod threshold_kind;
+pub mod charge_result;
+pub mod full_config;
+pub mod rounding_mode;
+pub mod subscription_summary;
+pub mod legacy_subscription;
+pub mod threshold_param;
\ No newline at end of file
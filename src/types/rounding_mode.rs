@@ -0,0 +1,10 @@
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+pub enum RoundingMode {
+    // Fractional part of the fee is dropped
+    Floor = 0,
+    // Fee is rounded to the nearest integer, halves are rounded up
+    HalfUp = 1
+}
@@ -1,6 +1,9 @@
 use soroban_sdk::{contracttype, Address, Bytes};
 
-use super::{subscription_status::SubscriptionStatus, ticker_asset::TickerAsset};
+use super::{
+    heartbeat_unit::HeartbeatUnit, subscription_status::SubscriptionStatus,
+    threshold_kind::ThresholdKind, ticker_asset::TickerAsset,
+};
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -13,16 +16,50 @@ pub struct Subscription {
     pub base: TickerAsset,
     // Quote symbol
     pub quote: TickerAsset,
-    // Price movement threshold that triggers subscription, in ‰
-    pub threshold: u32,
-    // Interval of periodic invocations, in minutes
+    // Price movement threshold that triggers subscription
+    pub threshold: ThresholdKind,
+    // Interval of periodic invocations, in heartbeat units
     pub heartbeat: u32,
+    // Heartbeat unit
+    pub heartbeat_unit: HeartbeatUnit,
     // The webhook.
     pub webhook: Bytes,
+    // Preferred notification payload format
+    pub format: u32,
     // Current outstanding subscription balance
     pub balance: u64,
+    // Part of the balance that can't be charged and is always refunded on cancellation
+    pub reserve: u64,
+    // Whether charged events are published for this subscription
+    pub emit_charge_events: bool,
+    // Whether the subscription is cancelled with a refund instead of being suspended
+    pub auto_cancel_on_suspend: bool,
+    // Number of per-subscription triggers published
+    pub trigger_count: u64,
+    // Timestamp of the last per-subscription trigger
+    pub last_triggered: Option<u64>,
     // Current status
     pub status: SubscriptionStatus,
-    // Last updated timestamp
-    pub updated: u64
+    // Last updated timestamp, changes on any subscription mutation
+    pub updated: u64,
+    // Timestamp up to which retention fees have been charged
+    pub last_charged: u64,
+    // Timestamp when the subscription has been suspended
+    pub suspended_at: Option<u64>,
+    // Timestamp when the subscription has been paused due to an oracle source outage
+    pub outage_at: Option<u64>,
+    // Timestamp when the owner requested a recoverable cancellation
+    pub cancelled_at: Option<u64>,
+    // Timestamp when the subscription stops regardless of the balance
+    pub expires_at: Option<u64>,
+    // Proposed new owner address awaiting the transfer acceptance
+    pub pending_owner: Option<Address>,
+    // Maximum daily retention fee the owner is willing to pay
+    pub max_acceptable_fee: Option<u64>,
+    // Start of the daily notification window, in minutes from midnight UTC
+    pub active_from_minute: Option<u32>,
+    // End of the daily notification window, in minutes from midnight UTC
+    pub active_to_minute: Option<u32>,
+    // Whether the subscription belongs to the zero-fee promotional tier
+    pub free_tier: bool
 }
@@ -1,6 +1,8 @@
 use soroban_sdk::{contracttype, Address, Bytes};
 
-use super::ticker_asset::TickerAsset;
+use super::{
+    heartbeat_unit::HeartbeatUnit, threshold_param::ThresholdParam, ticker_asset::TickerAsset,
+};
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -13,10 +15,24 @@ pub struct SubscriptionInitParams {
     pub base: TickerAsset,
     // Quote symbol
     pub quote: TickerAsset,
-    // Price movement threshold that triggers subscription, in ‰
-    pub threshold: u32,
-    // Interval of periodic invocations, in minutes
-    pub heartbeat: u32,
+    // Price movement threshold that triggers subscription (contract default is used if not set)
+    pub threshold: ThresholdParam,
+    // Interval of periodic invocations, in heartbeat units (contract default is used if not set)
+    pub heartbeat: Option<u32>,
+    // Heartbeat unit
+    pub heartbeat_unit: HeartbeatUnit,
     // Encrypted webhook URL where trigger notifications get POSTed
     pub webhook: Bytes,
+    // Part of the balance that can't be charged and is always refunded on cancellation
+    pub reserve: u64,
+    // Optional nonce for deterministic subscription ID derivation (sequential ID is assigned if not set)
+    pub id_nonce: Option<u64>,
+    // Preferred notification payload format
+    pub format: u32,
+    // Optional timestamp when the subscription stops and the remaining balance gets refunded, in milliseconds
+    pub expires_at: Option<u64>,
+    // Optional start of the daily notification window, in minutes from midnight UTC
+    pub active_from_minute: Option<u32>,
+    // Optional end of the daily notification window, in minutes from midnight UTC
+    pub active_to_minute: Option<u32>,
 }
@@ -1,11 +1,16 @@
 use soroban_sdk::contracttype;
 
-
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq, Copy)]
 pub enum SubscriptionStatus {
     // Subscription tracks price feeds and triggers notifications
     Active = 0,
     // Subscription won't receive updates nor trigger notifications
-    Suspended = 1
+    Suspended = 1,
+    // Subscription has been suspended for too long and can't be revived
+    Expired = 2,
+    // Subscription has been cancelled by the owner and can be restored within the recovery window
+    PendingDeletion = 3,
+    // Subscription depends on an offline oracle source and is paused until the source recovers
+    SourceOutage = 4
 }
\ No newline at end of file
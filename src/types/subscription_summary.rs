@@ -0,0 +1,22 @@
+use soroban_sdk::{contracttype, Address};
+
+use super::{subscription_status::SubscriptionStatus, ticker_asset::TickerAsset};
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+
+// Bookkeeping snapshot of a subscription record, outlives the record to reconcile it once it expires
+pub struct SubscriptionSummary {
+    // Address of account that owns the subscription
+    pub owner: Address,
+    // Base symbol
+    pub base: TickerAsset,
+    // Quote symbol
+    pub quote: TickerAsset,
+    // Subscription status
+    pub status: SubscriptionStatus,
+    // Current subscription balance
+    pub balance: u64,
+    // Whether the subscription is charged zero fees
+    pub free_tier: bool
+}
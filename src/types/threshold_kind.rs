@@ -0,0 +1,12 @@
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+
+// Price movement threshold that triggers subscription
+pub enum ThresholdKind {
+    // Price movement relative to the last price, in ‰
+    Relative(u32),
+    // Absolute price movement, in quote asset price units
+    Absolute(u64)
+}
@@ -0,0 +1,14 @@
+use soroban_sdk::contracttype;
+
+use super::threshold_kind::ThresholdKind;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+
+// Price movement threshold requested in subscription creation params
+pub enum ThresholdParam {
+    // Contract-wide default threshold
+    Default,
+    // Explicit threshold
+    Custom(ThresholdKind)
+}